@@ -0,0 +1,23 @@
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+// x, y: the game size in pixels. z: how dark the gaps between scanlines get.
+@group(1) @binding(0) var<uniform> screen: vec4<f32>;
+@group(1) @binding(1) var game_texture: texture_2d<f32>;
+@group(1) @binding(2) var game_sampler: sampler;
+
+const TAU: f32 = 6.28318530718;
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(game_texture, game_sampler, mesh.uv);
+
+    // Brightest in the middle of each game pixel row, darkest between rows.
+    let row = fract(mesh.uv.y * screen.y);
+    let scanline = 1.0 - screen.z * (0.5 + 0.5 * cos(row * TAU));
+
+    // Darken the corners a little, like a curved tube.
+    let from_center = (mesh.uv - 0.5) * 2.0;
+    let vignette = clamp(1.0 - 0.15 * dot(from_center, from_center), 0.0, 1.0);
+
+    return vec4<f32>(color.rgb * scanline * vignette, 1.0);
+}
@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+
+use crate::settings::Settings;
+
+// Shakes a camera around where it was placed. Trauma goes from 0 to 1 and
+// the shake grows with its square, so small hits barely move the camera.
+#[derive(Component, Default)]
+pub struct CameraShake {
+    trauma: f32,
+    // What was added to the transform last frame, so it can be taken back off.
+    offset: Vec2,
+    angle: f32
+}
+
+// Adds trauma to every shaking camera.
+#[derive(Event, Clone, Copy)]
+pub struct ScreenShake(pub f32);
+
+// Freezes the game for a moment to make a hit land harder.
+#[derive(Event, Clone, Copy)]
+pub struct HitStop(pub f32);
+
+// Real seconds left on the current hit-stop.
+#[derive(Resource, Default)]
+struct HitStopTimer(f32);
+
+const MAX_OFFSET: f32 = 8.0;
+const MAX_ANGLE: f32 = 0.05;
+const TRAUMA_DECAY: f32 = 1.5;
+
+pub struct CameraFxPlugin;
+
+impl Plugin for CameraFxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ScreenShake>()
+            .add_event::<HitStop>()
+            .init_resource::<HitStopTimer>()
+            .add_systems(PostUpdate, (
+                    add_trauma,
+                    shake_cameras
+                ).chain()
+                .before(bevy::transform::TransformSystem::TransformPropagate)
+            )
+            .add_systems(Last, hit_stop);
+    }
+}
+
+fn add_trauma(
+    mut shakes: EventReader<ScreenShake>,
+    settings: Res<Settings>,
+    mut cameras: Query<&mut CameraShake>
+) {
+    for ScreenShake(trauma) in shakes.read() {
+        for mut shake in &mut cameras {
+            shake.trauma = (shake.trauma + trauma * settings.screen_shake).min(1.0);
+        }
+    }
+}
+
+fn shake_cameras(
+    time: Res<Time<Real>>,
+    mut cameras: Query<(&mut CameraShake, &mut Transform)>
+) {
+    // Real time, so the camera keeps shaking through a hit-stop.
+    let t = time.elapsed_seconds();
+    for (mut shake, mut transform) in &mut cameras {
+        transform.translation -= shake.offset.extend(0.0);
+        transform.rotate_z(-shake.angle);
+
+        shake.trauma = (shake.trauma - TRAUMA_DECAY * time.delta_seconds()).max(0.0);
+        let amount = shake.trauma * shake.trauma;
+        // Overlapping sine waves make a cheap, smooth stand-in for noise.
+        let noise = |seed: f32| (t * 37.0 + seed).sin() * 0.6 + (t * 71.0 + seed * 2.3).sin() * 0.4;
+        shake.offset = Vec2::new(noise(0.0), noise(11.0)) * MAX_OFFSET * amount;
+        shake.angle = noise(23.0) * MAX_ANGLE * amount;
+
+        transform.translation += shake.offset.extend(0.0);
+        transform.rotate_z(shake.angle);
+    }
+}
+
+// Runs last so a hit-stop started this frame doesn't change the delta of
+// systems that already ran.
+fn hit_stop(
+    mut stops: EventReader<HitStop>,
+    settings: Res<Settings>,
+    real_time: Res<Time<Real>>,
+    mut timer: ResMut<HitStopTimer>,
+    mut time: ResMut<Time<Virtual>>
+) {
+    let was_stopped = timer.0 > 0.0;
+    timer.0 = (timer.0 - real_time.delta_seconds()).max(0.0);
+    for HitStop(seconds) in stops.read() {
+        if settings.hit_stop {
+            timer.0 = timer.0.max(*seconds);
+        }
+    }
+
+    let stopped = timer.0 > 0.0;
+    if stopped && !was_stopped {
+        time.pause();
+    } else if !stopped && was_stopped {
+        time.unpause();
+    }
+}
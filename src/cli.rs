@@ -0,0 +1,195 @@
+use bevy::prelude::*;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use crate::difficulty::Difficulty;
+use crate::game::AppState;
+use crate::net::NetConfig;
+
+pub const USAGE: &str = "\
+Usage: bevy-jam-4 [options]
+
+  --state <name>       Start in main-menu, credits, settings, game, win, lose, results
+                       or endless-results
+  --seed <number>      Seed the match randomness
+  --difficulty <name>  easy, normal or hard
+  --record <path>      Record the match inputs to a replay file
+  --replay <path>      Play back a replay file instead of reading input, starting in
+                       the game unless --state says otherwise
+  --headless           Run without a window or renderer, quitting once the replay or
+                       match is over
+  --scale <number>     Start the window at this multiple of 320x240
+  --mute               Turn off all audio
+  --debug              Start with the debug tools on (needs --features debug)
+  --exec <path>        Run console commands from a file, one per line
+  --renderer <name>    auto, vulkan, metal, dx12, gl or none
+  --net-local <addr> --net-peer <addr> --net-slot <0|1>
+                       Offer online versus, see net.rs
+  --help               Show this and quit
+";
+
+// Launch options, so QA and automated tests can go straight to a scenario.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct Cli {
+    pub state: Option<AppState>,
+    pub seed: Option<u64>,
+    pub difficulty: Option<Difficulty>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub headless: bool,
+    pub scale: Option<u32>,
+    pub mute: bool,
+    pub debug: bool,
+    pub exec: Option<PathBuf>,
+    // Checked in init.rs, along with the environment and settings.
+    pub renderer: Option<String>,
+    // Set when both addresses were given.
+    pub net: Option<NetConfig>,
+    pub help: bool,
+    // Bad values, logged once logging is up.
+    pub warnings: Vec<String>
+}
+
+impl Cli {
+    pub fn from_args() -> Self {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Self {
+        let mut cli = Cli::default();
+        let mut net_local: Option<SocketAddr> = None;
+        let mut net_peer: Option<SocketAddr> = None;
+        let mut net_slot = 0;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // Both `--flag value` and `--flag=value` work.
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None)
+            };
+            let mut value = || inline.clone().or_else(|| args.next()).unwrap_or_default();
+            match flag.as_str() {
+                "--state" => {
+                    let name = value();
+                    cli.state = AppState::from_name(&name);
+                    if cli.state.is_none() {
+                        cli.warnings.push(format!("Unknown state \"{name}\""));
+                    }
+                },
+                "--seed" => {
+                    let seed = value();
+                    cli.seed = seed.parse().ok();
+                    if cli.seed.is_none() {
+                        cli.warnings.push(format!("Bad seed \"{seed}\", expected a whole number"));
+                    }
+                },
+                "--difficulty" => {
+                    let name = value();
+                    cli.difficulty = Difficulty::from_name(&name);
+                    if cli.difficulty.is_none() {
+                        cli.warnings.push(format!("Unknown difficulty \"{name}\""));
+                    }
+                },
+                "--record" => cli.record = Some(value().into()),
+                "--replay" => cli.replay = Some(value().into()),
+                "--exec" => cli.exec = Some(value().into()),
+                "--scale" => {
+                    let scale = value();
+                    cli.scale = scale.parse().ok().filter(|scale| *scale > 0);
+                    if cli.scale.is_none() {
+                        cli.warnings.push(format!("Bad window scale \"{scale}\", expected 1 or more"));
+                    }
+                },
+                "--renderer" => cli.renderer = Some(value()),
+                "--net-local" | "--net-peer" => {
+                    let addr = value();
+                    let parsed = addr.parse().ok();
+                    if parsed.is_none() {
+                        cli.warnings.push(format!("Bad address \"{addr}\" for {flag}, expected ip:port"));
+                    }
+                    if flag == "--net-local" {
+                        net_local = parsed;
+                    } else {
+                        net_peer = parsed;
+                    }
+                },
+                "--net-slot" => {
+                    let slot = value();
+                    match slot.parse().ok().filter(|slot| *slot <= 1) {
+                        Some(slot) => net_slot = slot,
+                        None => cli.warnings.push(format!("Bad netplay slot \"{slot}\", expected 0 or 1"))
+                    }
+                },
+                "--headless" => cli.headless = true,
+                "--mute" => cli.mute = true,
+                "--debug" => cli.debug = true,
+                "--help" | "-h" => cli.help = true,
+                // Most likely a typo, which would otherwise quietly start a
+                // normal match.
+                _ => cli.warnings.push(format!("Unknown option \"{flag}\", see --help"))
+            }
+        }
+        cli.net = match (net_local, net_peer) {
+            (Some(local), Some(peer)) => Some(NetConfig { local, peer, slot: net_slot }),
+            (None, None) => None,
+            _ => {
+                cli.warnings.push("Online versus needs both --net-local and --net-peer".into());
+                None
+            }
+        };
+        if cli.debug && !cfg!(feature = "debug") {
+            cli.warnings.push("--debug does nothing without a build with --features debug".into());
+        }
+        if cli.record.is_some() && cli.replay.is_some() {
+            cli.warnings.push("Can't record while playing a replay, only playing it".into());
+            cli.record = None;
+        }
+        cli
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Cli {
+        Cli::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parses_every_flag() {
+        let cli = parse(
+            "--state game --seed 42 --difficulty=hard --replay runs/a.replay --headless \
+            --scale 3 --mute --debug --renderer gl \
+            --net-local 127.0.0.1:7000 --net-peer=127.0.0.1:7001 --net-slot 1"
+        );
+        assert_eq!(cli.state, Some(AppState::InGame));
+        assert_eq!(cli.seed, Some(42));
+        assert_eq!(cli.difficulty, Some(Difficulty::Hard));
+        assert_eq!(cli.replay, Some(PathBuf::from("runs/a.replay")));
+        assert!(cli.headless && cli.mute && cli.debug);
+        assert_eq!(cli.scale, Some(3));
+        assert_eq!(cli.renderer.as_deref(), Some("gl"));
+        let net = cli.net.clone().expect("both addresses were given");
+        assert_eq!((net.local.port(), net.peer.port(), net.slot), (7000, 7001, 1));
+        // Only --debug warns, and only without the debug tools built in.
+        assert_eq!(cli.warnings.len(), usize::from(!cfg!(feature = "debug")));
+
+        assert_eq!(parse(""), Cli::default());
+    }
+
+    #[test]
+    fn warns_about_bad_values() {
+        let cli = parse("--state nowhere --seed -1 --scale 0 --record a --replay b");
+        assert_eq!(cli.state, None);
+        assert_eq!(cli.seed, None);
+        assert_eq!(cli.scale, None);
+        assert_eq!(cli.record, None);
+        assert_eq!(cli.warnings.len(), 4);
+
+        // Typos and half a netplay setup don't go unnoticed either.
+        let cli = parse("--seeed=4 --net-peer 127.0.0.1:7001 --net-slot 2");
+        assert_eq!(cli.net, None);
+        assert_eq!(cli.warnings.len(), 3);
+    }
+}
@@ -0,0 +1,444 @@
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+use crate::cli::Cli;
+use crate::game::{AppState, Netplay};
+
+pub const HELP: &str = "\
+health player|enemy <value>   set health
+god [on|off]                  players take no damage
+spawn snow|projectile <count> spawn right away
+rate snow <seconds>           time between snow tiles
+rate projectile <scale>       speed up enemy attacks
+win, lose, state <name>       jump to a state
+timescale <scale>             speed up or slow down the game
+debug colliders|inspector|stats|tuning
+clear, help";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthTarget {
+    Players,
+    Enemies
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Spawnable {
+    Snow,
+    Projectile
+}
+
+// A parsed console command. The game handles the ones about the match, the
+// console handles the rest.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub enum ConsoleCommand {
+    Health(HealthTarget, f32),
+    // None toggles.
+    God(Option<bool>),
+    Spawn(Spawnable, u32),
+    SnowRate(f32),
+    ProjectileRate(f32),
+    State(AppState),
+    TimeScale(f32),
+    Debug(String),
+    Clear,
+    Help
+}
+
+impl ConsoleCommand {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let words: Vec<String> = line.split_whitespace().map(|word| word.to_ascii_lowercase()).collect();
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        let number = |word: Option<&&str>| -> Result<f32, String> {
+            let word = word.ok_or("missing a number")?;
+            word.parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite() && *value >= 0.0)
+                .ok_or(format!("\"{word}\" isn't a number of 0 or more"))
+        };
+        let command = match words.as_slice() {
+            ["health", "player" | "players", rest @ ..] => ConsoleCommand::Health(HealthTarget::Players, number(rest.first())?),
+            ["health", "enemy" | "enemies" | "boss", rest @ ..] => ConsoleCommand::Health(HealthTarget::Enemies, number(rest.first())?),
+            ["god"] => ConsoleCommand::God(None),
+            ["god", "on"] => ConsoleCommand::God(Some(true)),
+            ["god", "off"] => ConsoleCommand::God(Some(false)),
+            ["spawn", what, rest @ ..] => {
+                let what = match *what {
+                    "snow" => Spawnable::Snow,
+                    "projectile" | "projectiles" => Spawnable::Projectile,
+                    _ => return Err(format!("can't spawn \"{what}\""))
+                };
+                // Enough to test with, not enough to hang the game.
+                let count = if rest.is_empty() { 1.0 } else { number(rest.first())? };
+                ConsoleCommand::Spawn(what, (count as u32).clamp(1, 100))
+            },
+            ["rate", "snow", rest @ ..] => ConsoleCommand::SnowRate(number(rest.first())?.max(0.01)),
+            ["rate", "projectile" | "projectiles", rest @ ..] => ConsoleCommand::ProjectileRate(number(rest.first())?),
+            ["win"] => ConsoleCommand::State(AppState::Win),
+            ["lose"] => ConsoleCommand::State(AppState::Lose),
+            ["state", name] => ConsoleCommand::State(AppState::from_name(name).ok_or(format!("no state called \"{name}\""))?),
+            ["timescale", rest @ ..] => ConsoleCommand::TimeScale(number(rest.first())?),
+            ["debug", tool] => ConsoleCommand::Debug(tool.to_string()),
+            ["clear"] => ConsoleCommand::Clear,
+            ["help"] => ConsoleCommand::Help,
+            [] => return Err("nothing to do".into()),
+            _ => return Err(format!("don't know \"{}\", try help", line.trim()))
+        };
+        Ok(command)
+    }
+
+    // Everything but the debug tools and the console's own commands changes
+    // the match.
+    pub fn is_cheat(&self) -> bool {
+        !matches!(self, ConsoleCommand::Debug(_) | ConsoleCommand::Clear | ConsoleCommand::Help)
+    }
+
+    // Like parse, but refuses cheats during netplay since the other side
+    // wouldn't see them and the game would desync.
+    pub fn parse_allowed(line: &str, online: bool) -> Result<Self, String> {
+        let command = ConsoleCommand::parse(line)?;
+        if online && command.is_cheat() {
+            return Err(format!("can't \"{}\" during netplay", line.trim()));
+        }
+        Ok(command)
+    }
+}
+
+// A line for the console's log, so command handlers can say what they did.
+#[derive(Event, Debug, Clone)]
+pub struct ConsoleOutput(pub String);
+
+#[derive(Resource, Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    log: VecDeque<String>
+}
+
+const MAX_LOG_LINES: usize = 12;
+
+impl Console {
+    fn print(&mut self, line: impl Into<String>) {
+        self.log.push_back(line.into());
+        while self.log.len() > MAX_LOG_LINES {
+            self.log.pop_front();
+        }
+    }
+}
+
+// Commands from --exec, run once everything is set up.
+#[derive(Resource, Default)]
+struct ConsoleScript {
+    lines: VecDeque<String>,
+    // After a state change the rest of the script waits until the new state
+    // has been entered, so the lines after `state game` have a match to
+    // adjust.
+    waiting_for: Option<AppState>
+}
+
+impl ConsoleScript {
+    fn parse(text: &str) -> Self {
+        Self {
+            lines: text.lines()
+                .map(str::trim)
+                // `#` starts a comment.
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(String::from)
+                .collect(),
+            waiting_for: None
+        }
+    }
+
+    // The lines that can run now in `state`, each with what it parsed to.
+    fn run(&mut self, state: AppState, online: bool) -> Vec<(String, Result<ConsoleCommand, String>)> {
+        let mut ran = Vec::new();
+        if let Some(waiting_for) = self.waiting_for {
+            if waiting_for != state {
+                return ran;
+            }
+            self.waiting_for = None;
+        }
+        while let Some(line) = self.lines.pop_front() {
+            let command = ConsoleCommand::parse_allowed(&line, online);
+            let state_change = match command {
+                Ok(ConsoleCommand::State(new_state)) => Some(new_state),
+                _ => None
+            };
+            ran.push((line, command));
+            if state_change.is_some() {
+                self.waiting_for = state_change;
+                break;
+            }
+        }
+        ran
+    }
+}
+
+#[derive(Component)]
+struct ConsoleUi;
+
+#[derive(Component)]
+struct ConsoleText;
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ConsoleCommand>()
+            .add_event::<ConsoleOutput>()
+            .init_resource::<Console>()
+            .init_resource::<ConsoleScript>()
+            .add_systems(Startup, (
+                    load_script,
+                    setup_console
+                )
+            )
+            // Before anything reads the keyboard, so typing doesn't move the
+            // players around.
+            .add_systems(PreUpdate,
+                type_in_console.after(bevy::input::InputSystem)
+            )
+            .add_systems(Update, (
+                    run_script,
+                    run_console_commands,
+                    print_console_output,
+                    update_console
+                ).chain()
+            );
+    }
+}
+
+fn load_script(
+    cli: Res<Cli>,
+    mut script: ResMut<ConsoleScript>
+) {
+    let Some(path) = &cli.exec else {
+        return;
+    };
+    match std::fs::read_to_string(path) {
+        Ok(text) => *script = ConsoleScript::parse(&text),
+        Err(err) => error!("couldn't read console script {}: {err}", path.display())
+    }
+}
+
+fn setup_console(mut commands: Commands) {
+    commands.spawn((
+        Name::new("Console"),
+        ConsoleUi,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(0.0),
+                width: Val::Percent(100.0),
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        // Over every screen's UI.
+        ZIndex::Global(900)
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            ConsoleText,
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 14.0,
+                    color: Color::WHITE,
+                    ..default()
+                }
+            )
+        ));
+    });
+}
+
+fn type_in_console(
+    mut console: ResMut<Console>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut console_commands: EventWriter<ConsoleCommand>,
+    netplay: Option<Res<Netplay>>
+) {
+    if keys.just_pressed(KeyCode::Grave) {
+        console.open = !console.open;
+        keys.reset_all();
+        characters.clear();
+        return;
+    }
+    if !console.open {
+        characters.clear();
+        return;
+    }
+
+    for character in characters.read() {
+        if !character.char.is_control() && character.char != '`' {
+            console.input.push(character.char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut console.input);
+        console.print(format!("> {line}"));
+        match ConsoleCommand::parse_allowed(&line, netplay.is_some()) {
+            Ok(command) => console_commands.send(command),
+            Err(err) => console.print(err)
+        }
+    }
+    // The game doesn't see keys pressed while typing.
+    keys.reset_all();
+}
+
+fn run_script(
+    mut script: ResMut<ConsoleScript>,
+    mut console: ResMut<Console>,
+    mut console_commands: EventWriter<ConsoleCommand>,
+    state: Res<State<AppState>>,
+    netplay: Option<Res<Netplay>>
+) {
+    for (line, command) in script.run(*state.get(), netplay.is_some()) {
+        console.print(format!("> {line}"));
+        match command {
+            Ok(command) => console_commands.send(command),
+            Err(err) => {
+                warn!("console script: {err}");
+                console.print(err);
+            }
+        }
+    }
+}
+
+// The commands that aren't about the match.
+fn run_console_commands(
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut console: ResMut<Console>,
+    mut time: ResMut<Time<Virtual>>,
+    #[cfg(feature = "debug")]
+    mut tools: ResMut<crate::debug::DebugTools>
+) {
+    for command in console_commands.read() {
+        match command {
+            ConsoleCommand::TimeScale(scale) => {
+                time.set_relative_speed(*scale);
+                console.print(format!("time scale is {scale}"));
+            },
+            ConsoleCommand::Debug(tool) => {
+                #[cfg(feature = "debug")]
+                {
+                    let tool_enabled = match tool.as_str() {
+                        "colliders" => Some(&mut tools.colliders),
+                        "inspector" => Some(&mut tools.inspector),
+                        "stats" => Some(&mut tools.stats),
+                        "tuning" => Some(&mut tools.tuning),
+                        _ => None
+                    };
+                    match tool_enabled {
+                        Some(enabled) => {
+                            *enabled = !*enabled;
+                            let state = if *enabled { "on" } else { "off" };
+                            console.print(format!("{tool} {state}"));
+                        },
+                        None => console.print(format!("no debug tool called \"{tool}\""))
+                    }
+                }
+                #[cfg(not(feature = "debug"))]
+                console.print(format!("can't show {tool}, built without --features debug"));
+            },
+            ConsoleCommand::Clear => console.log.clear(),
+            ConsoleCommand::Help => {
+                for line in HELP.lines() {
+                    console.print(line);
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+fn print_console_output(
+    mut output: EventReader<ConsoleOutput>,
+    mut console: ResMut<Console>
+) {
+    for ConsoleOutput(line) in output.read() {
+        console.print(line.clone());
+    }
+}
+
+fn update_console(
+    console: Res<Console>,
+    mut uis: Query<&mut Visibility, With<ConsoleUi>>,
+    mut texts: Query<&mut Text, With<ConsoleText>>
+) {
+    if !console.is_changed() {
+        return;
+    }
+    let shown = if console.open { Visibility::Inherited } else { Visibility::Hidden };
+    for mut visibility in &mut uis {
+        if *visibility != shown {
+            *visibility = shown;
+        }
+    }
+    for mut text in &mut texts {
+        let mut value: String = console.log.iter().map(|line| format!("{line}\n")).collect();
+        value.push_str(&format!("> {}_", console.input));
+        text.sections[0].value = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(ConsoleCommand::parse("health player 50"), Ok(ConsoleCommand::Health(HealthTarget::Players, 50.0)));
+        assert_eq!(ConsoleCommand::parse("  HEALTH Boss 0 "), Ok(ConsoleCommand::Health(HealthTarget::Enemies, 0.0)));
+        assert_eq!(ConsoleCommand::parse("god"), Ok(ConsoleCommand::God(None)));
+        assert_eq!(ConsoleCommand::parse("god off"), Ok(ConsoleCommand::God(Some(false))));
+        assert_eq!(ConsoleCommand::parse("spawn snow 5"), Ok(ConsoleCommand::Spawn(Spawnable::Snow, 5)));
+        assert_eq!(ConsoleCommand::parse("spawn projectile"), Ok(ConsoleCommand::Spawn(Spawnable::Projectile, 1)));
+        assert_eq!(ConsoleCommand::parse("spawn snow 100000"), Ok(ConsoleCommand::Spawn(Spawnable::Snow, 100)));
+        assert_eq!(ConsoleCommand::parse("rate snow 0.2"), Ok(ConsoleCommand::SnowRate(0.2)));
+        assert_eq!(ConsoleCommand::parse("rate projectile 3"), Ok(ConsoleCommand::ProjectileRate(3.0)));
+        assert_eq!(ConsoleCommand::parse("win"), Ok(ConsoleCommand::State(AppState::Win)));
+        assert_eq!(ConsoleCommand::parse("state lose"), Ok(ConsoleCommand::State(AppState::Lose)));
+        assert_eq!(ConsoleCommand::parse("timescale 0.25"), Ok(ConsoleCommand::TimeScale(0.25)));
+        assert_eq!(ConsoleCommand::parse("debug stats"), Ok(ConsoleCommand::Debug("stats".into())));
+    }
+
+    #[test]
+    fn rejects_bad_commands() {
+        assert!(ConsoleCommand::parse("").is_err());
+        assert!(ConsoleCommand::parse("health player").is_err());
+        assert!(ConsoleCommand::parse("health player -5").is_err());
+        assert!(ConsoleCommand::parse("timescale NaN").is_err());
+        assert!(ConsoleCommand::parse("spawn bears 2").is_err());
+        assert!(ConsoleCommand::parse("state moon").is_err());
+        assert!(ConsoleCommand::parse("fly").is_err());
+    }
+
+    #[test]
+    fn refuses_cheats_during_netplay() {
+        assert!(ConsoleCommand::parse_allowed("god on", true).is_err());
+        assert!(ConsoleCommand::parse_allowed("timescale 2", true).is_err());
+        assert!(ConsoleCommand::parse_allowed("state win", true).is_err());
+        assert_eq!(ConsoleCommand::parse_allowed("debug stats", true), Ok(ConsoleCommand::Debug("stats".into())));
+        assert_eq!(ConsoleCommand::parse_allowed("god on", false), Ok(ConsoleCommand::God(Some(true))));
+    }
+
+    #[test]
+    fn scripts_wait_for_state_changes() {
+        let mut script = ConsoleScript::parse("# Start a match, then set it up.\nstate game\n\nhealth boss 10\ngod on\nwin");
+        let mut run = |state: AppState| -> Vec<String> {
+            script.run(state, false).into_iter().map(|(line, _)| line).collect()
+        };
+        assert_eq!(run(AppState::MainMenu), ["state game"]);
+        // Still on the menu, so there's no match to adjust yet.
+        assert!(run(AppState::MainMenu).is_empty());
+        assert_eq!(run(AppState::InGame), ["health boss 10", "god on", "win"]);
+        assert!(run(AppState::Win).is_empty());
+    }
+}
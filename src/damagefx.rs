@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+use bevy_tweening::{lens::*, *};
+use std::time::Duration;
+
+// Sent whenever something takes damage, for effects to react to.
+#[derive(Event, Clone, Copy)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: f32,
+    // Where the hit landed, in world space.
+    pub position: Vec2,
+    pub crit: bool
+}
+
+// Flashes a sprite between white and red, then puts its color back. Pub so
+// anything else that colors sprites can leave flashing ones alone.
+#[derive(Component)]
+pub struct HitFlash {
+    timer: Timer,
+    color: Color
+}
+
+// Despawns a damage number once its tweens are done.
+#[derive(Component)]
+struct DamageNumber(Timer);
+
+const FLASH_SECS: f32 = 0.15;
+// How long each flash color is shown, roughly two frames at 60fps.
+const FLASH_FRAME_SECS: f32 = 0.035;
+const NUMBER_SECS: f32 = 0.7;
+// Sprite colors multiply the texture, so white on its own changes nothing.
+// Going well over 1 washes the texture out to white instead.
+const FLASH_WHITE: Color = Color::rgb(4.0, 4.0, 4.0);
+
+pub struct DamageFxPlugin;
+
+impl Plugin for DamageFxPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_systems(Update, (
+                    start_hit_flash,
+                    spawn_damage_numbers,
+                    hit_flash,
+                    despawn_damage_numbers
+                ).chain()
+            );
+    }
+}
+
+fn start_hit_flash(
+    mut commands: Commands,
+    mut damage: EventReader<DamageEvent>,
+    mut sprites: Query<(&Sprite, Option<&mut HitFlash>)>,
+    children: Query<&Children>
+) {
+    for event in damage.read() {
+        // Targets without a sprite of their own, like the player capsules,
+        // flash their child sprites instead.
+        let targets: Vec<Entity> = if sprites.contains(event.target) {
+            vec![event.target]
+        } else {
+            children.get(event.target)
+                .map(|children| children.iter().copied().filter(|child| sprites.contains(*child)).collect())
+                .unwrap_or_default()
+        };
+        for target in targets {
+            let Ok((sprite, flash)) = sprites.get_mut(target) else {
+                continue;
+            };
+            match flash {
+                // Already flashing, so keep the original color and start over.
+                Some(mut flash) => flash.timer.reset(),
+                None => {
+                    commands.entity(target).insert(HitFlash {
+                        timer: Timer::from_seconds(FLASH_SECS, TimerMode::Once),
+                        color: sprite.color
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn hit_flash(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut flashes: Query<(Entity, &mut HitFlash, &mut Sprite)>
+) {
+    for (entity, mut flash, mut sprite) in &mut flashes {
+        flash.timer.tick(time.delta());
+        if flash.timer.finished() {
+            sprite.color = flash.color;
+            commands.entity(entity).remove::<HitFlash>();
+            continue;
+        }
+        let frame = (flash.timer.elapsed_secs() / FLASH_FRAME_SECS) as u32;
+        sprite.color = if frame % 2 == 0 { FLASH_WHITE } else { Color::RED };
+    }
+}
+
+fn spawn_damage_numbers(
+    mut commands: Commands,
+    mut damage: EventReader<DamageEvent>
+) {
+    for event in damage.read() {
+        // Crits are bigger, yellow and float up further. Hits a shield
+        // soaked up completely show a small blue 0.
+        let (font_size, color, rise) = if event.amount <= 0.0 {
+            (8.0, Color::rgb(0.6, 0.8, 1.0), 12.0)
+        } else if event.crit {
+            (16.0, Color::rgb(1.0, 0.85, 0.2), 28.0)
+        } else {
+            (10.0, Color::WHITE, 18.0)
+        };
+        // Anything that got through shows at least 1.
+        let amount = if event.amount <= 0.0 { 0.0 } else { event.amount.max(1.0) };
+        let start = event.position.extend(600.0);
+        let duration = Duration::from_secs_f32(NUMBER_SECS);
+        let float_up = Tween::new(
+            EaseFunction::QuadraticOut,
+            duration,
+            TransformPositionLens {
+                start,
+                end: start + Vec3::Y * rise
+            }
+        );
+        let fade = Tween::new(
+            EaseFunction::QuadraticIn,
+            duration,
+            TextColorLens {
+                start: color,
+                end: color.with_a(0.0),
+                section: 0
+            }
+        );
+
+        commands.spawn((
+            Name::new("DamageNumber"),
+            DamageNumber(Timer::from_seconds(NUMBER_SECS, TimerMode::Once)),
+            Text2dBundle {
+                text: Text::from_section(
+                    format!("{amount:.0}"),
+                    TextStyle {
+                        font_size,
+                        color,
+                        ..default()
+                    }
+                ),
+                transform: Transform::from_translation(start),
+                ..default()
+            },
+            Animator::new(float_up),
+            Animator::new(fade)
+        ));
+    }
+}
+
+fn despawn_damage_numbers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut numbers: Query<(Entity, &mut DamageNumber)>
+) {
+    for (entity, mut number) in &mut numbers {
+        if number.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
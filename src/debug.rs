@@ -0,0 +1,208 @@
+use bevy::prelude::*;
+use bevy::diagnostic::{DiagnosticsStore, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin};
+use bevy::reflect::ReflectRef;
+use bevy::window::PrimaryWindow;
+use bevy_inspector_egui::bevy_egui::{EguiContext, EguiPlugin};
+use bevy_inspector_egui::egui;
+use bevy_inspector_egui::quick::WorldInspectorPlugin;
+use bevy_xpbd_2d::prelude::*;
+
+use crate::cli::Cli;
+
+// Debug tools, only built with `cargo run --features debug`. All of them
+// start on with --debug.
+//   F1: physics colliders
+//   F2: world inspector
+//   F3: FPS and entity count
+//   F4: tuning panel
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DebugTools {
+    pub colliders: bool,
+    pub inspector: bool,
+    pub stats: bool,
+    pub tuning: bool
+}
+
+impl DebugTools {
+    pub fn all(on: bool) -> Self {
+        Self {
+            colliders: on,
+            inspector: on,
+            stats: on,
+            tuning: on
+        }
+    }
+}
+
+// What the tuning panel shows, looked up by name in the type registry so
+// this doesn't need to know about the game's types. They have to be
+// registered and reflect Resource or Component.
+const TUNING_RESOURCES: [&str; 2] = ["SnowConfig", "ProjectileConfig"];
+const TUNING_COMPONENTS: [&str; 2] = ["Speed", "EnemyHealth"];
+
+#[derive(Component)]
+struct StatsOverlay;
+
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        let on = app.world.get_resource::<Cli>().is_some_and(|cli| cli.debug);
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        app.insert_resource(DebugTools::all(on))
+            .add_plugins((
+                PhysicsDebugPlugin::default(),
+                WorldInspectorPlugin::new().run_if(|tools: Res<DebugTools>| tools.inspector),
+                FrameTimeDiagnosticsPlugin,
+                EntityCountDiagnosticsPlugin
+            ))
+            .add_systems(Startup, setup_stats_overlay)
+            .add_systems(Update, (
+                    toggle_tools,
+                    show_colliders,
+                    update_stats_overlay,
+                    tuning_panel.run_if(|tools: Res<DebugTools>| tools.tuning)
+                ).chain()
+            );
+    }
+}
+
+fn toggle_tools(
+    keys: Res<Input<KeyCode>>,
+    mut tools: ResMut<DebugTools>
+) {
+    if keys.just_pressed(KeyCode::F1) {
+        tools.colliders = !tools.colliders;
+    }
+    if keys.just_pressed(KeyCode::F2) {
+        tools.inspector = !tools.inspector;
+    }
+    if keys.just_pressed(KeyCode::F3) {
+        tools.stats = !tools.stats;
+    }
+    if keys.just_pressed(KeyCode::F4) {
+        tools.tuning = !tools.tuning;
+    }
+}
+
+// Colliders are drawn with gizmos, and nothing else uses them.
+fn show_colliders(
+    tools: Res<DebugTools>,
+    mut gizmos: ResMut<GizmoConfig>
+) {
+    if tools.is_changed() {
+        gizmos.enabled = tools.colliders;
+    }
+}
+
+fn setup_stats_overlay(mut commands: Commands) {
+    commands.spawn((
+        Name::new("StatsOverlay"),
+        StatsOverlay,
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 16.0,
+                color: Color::YELLOW,
+                ..default()
+            }
+        ).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(4.0),
+            right: Val::Px(4.0),
+            ..default()
+        }),
+        // Over every screen's UI.
+        ZIndex::Global(1000)
+    ));
+}
+
+fn update_stats_overlay(
+    tools: Res<DebugTools>,
+    diagnostics: Res<DiagnosticsStore>,
+    mut overlays: Query<(&mut Text, &mut Visibility), With<StatsOverlay>>
+) {
+    let value = |id| diagnostics.get(id).and_then(|diagnostic| diagnostic.smoothed()).unwrap_or(0.0);
+    for (mut text, mut visibility) in &mut overlays {
+        let shown = if tools.stats { Visibility::Inherited } else { Visibility::Hidden };
+        if *visibility != shown {
+            *visibility = shown;
+        }
+        if tools.stats {
+            text.sections[0].value = format!(
+                "FPS {:.0}  Entities {:.0}",
+                value(FrameTimeDiagnosticsPlugin::FPS),
+                value(EntityCountDiagnosticsPlugin::ENTITY_COUNT)
+            );
+        }
+    }
+}
+
+// A short, readable line for a reflected value.
+fn describe(value: &dyn Reflect) -> String {
+    if let Some(timer) = value.downcast_ref::<Timer>() {
+        return format!("{:.2}s of {:.2}s", timer.elapsed_secs(), timer.duration().as_secs_f32());
+    }
+    let fields: Vec<String> = match value.reflect_ref() {
+        ReflectRef::Struct(value) => (0..value.field_len())
+            .filter_map(|i| Some(format!("{}: {}", value.name_at(i)?, describe(value.field_at(i)?))))
+            .collect(),
+        ReflectRef::TupleStruct(value) => value.iter_fields().map(describe).collect(),
+        _ => return format!("{value:?}")
+    };
+    fields.join(", ")
+}
+
+// The current value of every tuning resource and component, by heading.
+fn tuning_lines(world: &World) -> Vec<(&'static str, Vec<String>)> {
+    let registry = world.resource::<AppTypeRegistry>().read();
+    let mut sections = Vec::new();
+    for name in TUNING_RESOURCES {
+        let value = registry.get_with_short_type_path(name)
+            .and_then(|registration| registration.data::<ReflectResource>())
+            .and_then(|resource| resource.reflect(world));
+        let line = match value {
+            Some(value) => describe(value),
+            None => "not in use".to_string()
+        };
+        sections.push((name, vec![line]));
+    }
+    for name in TUNING_COMPONENTS {
+        let Some(component) = registry.get_with_short_type_path(name)
+            .and_then(|registration| registration.data::<ReflectComponent>()) else {
+            sections.push((name, vec!["not registered".to_string()]));
+            continue;
+        };
+        let lines = world.iter_entities()
+            .filter_map(|entity| {
+                let value = component.reflect(entity)?;
+                let label = entity.get::<Name>()
+                    .map(|name| name.to_string())
+                    .unwrap_or_else(|| format!("{:?}", entity.id()));
+                Some(format!("{label}: {}", describe(value)))
+            })
+            .collect();
+        sections.push((name, lines));
+    }
+    sections
+}
+
+fn tuning_panel(world: &mut World) {
+    let sections = tuning_lines(world);
+    let Ok(mut context) = world
+        .query_filtered::<&mut EguiContext, With<PrimaryWindow>>()
+        .get_single_mut(world) else {
+        return;
+    };
+    let context = context.get_mut().clone();
+    egui::Window::new("Tuning").show(&context, |ui| {
+        for (name, lines) in sections {
+            ui.heading(name);
+            for line in lines {
+                ui.label(line);
+            }
+        }
+    });
+}
@@ -0,0 +1,226 @@
+use bevy::prelude::*;
+
+// How hard the bear hits back. Picked on the main menu or with --difficulty.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard
+}
+
+impl Difficulty {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "easy" => Some(Difficulty::Easy),
+            "normal" => Some(Difficulty::Normal),
+            "hard" => Some(Difficulty::Hard),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard"
+        }
+    }
+
+    // The next preset, wrapping back round to Easy.
+    pub fn next(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy
+        }
+    }
+
+    // Scales the damage players take from projectiles.
+    pub fn damage_factor(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.6,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.5
+        }
+    }
+
+    // Scales how often enemies throw projectiles.
+    pub fn projectile_rate(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.75,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.3
+        }
+    }
+
+    // Scales how fast enemies move.
+    pub fn enemy_speed_factor(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.8,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.2
+        }
+    }
+
+    // Scales how much punishment enemies can take.
+    pub fn enemy_health_factor(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.75,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.4
+        }
+    }
+}
+
+// How long each stretch of the match is judged over.
+const ADAPT_WINDOW_SECS: f32 = 10.0;
+const ADAPT_STEP: f32 = 0.1;
+const MIN_ADAPT_FACTOR: f32 = 0.7;
+const MAX_ADAPT_FACTOR: f32 = 1.4;
+// A quarter of a player's health in one window means they're struggling.
+const STRUGGLING_DAMAGE: f32 = 25.0;
+const CRUISING_DAMAGE: f32 = 5.0;
+const CRUISING_HIT_RATIO: f32 = 0.5;
+
+// Optional adaptive mode, on top of the preset. Every few seconds it looks
+// at how much damage the players took and how many of their throws hit,
+// and nudges enemy spawns and attacks up or down.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct AdaptiveDifficulty {
+    pub enabled: bool,
+    // Multiplies enemy spawn and attack rates. Always 1 while disabled.
+    pub factor: f32,
+    elapsed: f32,
+    damage_taken: f32,
+    throws: u32,
+    hits: u32
+}
+
+impl Default for AdaptiveDifficulty {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            factor: 1.0,
+            elapsed: 0.0,
+            damage_taken: 0.0,
+            throws: 0,
+            hits: 0
+        }
+    }
+}
+
+impl AdaptiveDifficulty {
+    // Back to the preset for a new match.
+    pub fn restart(&mut self) {
+        *self = Self {
+            enabled: self.enabled,
+            ..default()
+        };
+    }
+
+    pub fn record_damage(&mut self, amount: f32) {
+        self.damage_taken += amount;
+    }
+
+    pub fn record_throw(&mut self) {
+        self.throws += 1;
+    }
+
+    pub fn record_hit(&mut self) {
+        self.hits += 1;
+    }
+
+    // Returns true when the factor changed at the end of a window.
+    pub fn tick(&mut self, delta_secs: f32) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        self.elapsed += delta_secs;
+        if self.elapsed < ADAPT_WINDOW_SECS {
+            return false;
+        }
+
+        let hit_ratio = if self.throws == 0 { 0.0 } else { self.hits as f32 / self.throws as f32 };
+        let old = self.factor;
+        if self.damage_taken >= STRUGGLING_DAMAGE {
+            self.factor -= ADAPT_STEP;
+        } else if self.damage_taken < CRUISING_DAMAGE && self.throws >= 3 && hit_ratio >= CRUISING_HIT_RATIO {
+            self.factor += ADAPT_STEP;
+        }
+        self.factor = self.factor.clamp(MIN_ADAPT_FACTOR, MAX_ADAPT_FACTOR);
+
+        self.elapsed = 0.0;
+        self.damage_taken = 0.0;
+        self.throws = 0;
+        self.hits = 0;
+        self.factor != old
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(adaptive: &mut AdaptiveDifficulty, damage: f32, throws: u32, hits: u32) -> bool {
+        adaptive.record_damage(damage);
+        for _ in 0..throws {
+            adaptive.record_throw();
+        }
+        for _ in 0..hits {
+            adaptive.record_hit();
+        }
+        adaptive.tick(ADAPT_WINDOW_SECS)
+    }
+
+    #[test]
+    fn presets_cycle_and_scale_together() {
+        let mut difficulty = Difficulty::Easy;
+        for _ in 0..3 {
+            let next = difficulty.next();
+            if next != Difficulty::Easy {
+                assert!(next.damage_factor() > difficulty.damage_factor());
+                assert!(next.projectile_rate() > difficulty.projectile_rate());
+                assert!(next.enemy_speed_factor() > difficulty.enemy_speed_factor());
+                assert!(next.enemy_health_factor() > difficulty.enemy_health_factor());
+            }
+            assert_eq!(Difficulty::from_name(next.name()), Some(next));
+            difficulty = next;
+        }
+        assert_eq!(difficulty, Difficulty::Easy);
+    }
+
+    #[test]
+    fn adapts_to_recent_performance() {
+        let mut adaptive = AdaptiveDifficulty::default();
+        // Does nothing while disabled.
+        assert!(!window(&mut adaptive, 0.0, 10, 10));
+        assert_eq!(adaptive.factor, 1.0);
+
+        adaptive.enabled = true;
+        // Waits for the end of the window.
+        assert!(!adaptive.tick(ADAPT_WINDOW_SECS / 2.0));
+        adaptive.restart();
+
+        // Taking lots of damage eases off.
+        assert!(window(&mut adaptive, 30.0, 4, 4));
+        assert!((adaptive.factor - 0.9).abs() < 1e-4);
+        // Unhurt and hitting most throws ramps up.
+        assert!(window(&mut adaptive, 0.0, 4, 3));
+        assert!(window(&mut adaptive, 0.0, 4, 3));
+        assert!((adaptive.factor - 1.1).abs() < 1e-4);
+        // Unhurt but missing, or in between, stays put.
+        assert!(!window(&mut adaptive, 0.0, 4, 1));
+        assert!(!window(&mut adaptive, 10.0, 4, 4));
+
+        // Never strays too far from the preset.
+        for _ in 0..20 {
+            window(&mut adaptive, 100.0, 0, 0);
+        }
+        assert_eq!(adaptive.factor, MIN_ADAPT_FACTOR);
+
+        adaptive.restart();
+        assert!(adaptive.enabled);
+        assert_eq!(adaptive.factor, 1.0);
+    }
+}
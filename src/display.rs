@@ -0,0 +1,382 @@
+use bevy::prelude::*;
+use bevy::core_pipeline::clear_color::ClearColorConfig;
+use bevy::reflect::TypePath;
+use bevy::render::camera::{RenderTarget, ScalingMode, Viewport};
+use bevy::render::render_resource::{
+    AsBindGroup, Extent3d, ShaderRef, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages
+};
+use bevy::render::texture::ImageSampler;
+use bevy::render::view::RenderLayers;
+use bevy::sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle};
+use bevy::window::{PrimaryWindow, WindowMode};
+
+use crate::settings::Settings;
+
+// The game is drawn at this size and scaled up by whole numbers.
+pub const GAME_SIZE: UVec2 = UVec2::new(320, 240);
+
+// UI is laid out in units of half a game pixel, so a 640x480 layout always
+// covers the game area.
+const UI_UNITS_PER_PIXEL: f32 = 2.0;
+
+// Only drawn to by the extra letterbox cameras, which have nothing to draw
+// but the UI.
+const LETTERBOX_LAYER: u8 = 31;
+// Holds the quad the game image is drawn on.
+const UPSCALE_LAYER: u8 = 30;
+
+// Draws the game into a GAME_SIZE image, which is then shown at a whole
+// multiple of its size in the middle of the window, with black bars around it.
+#[derive(Component)]
+pub struct PixelCamera;
+
+// Draws the game image to the window.
+#[derive(Component)]
+struct UpscaleCamera;
+
+// One of the quads showing the game image. Only the one matching the CRT
+// setting is visible.
+#[derive(Component)]
+struct UpscaledGame {
+    crt: bool
+}
+
+// Scanlines and a dark vignette over the game image.
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+struct CrtMaterial {
+    // x, y: the game size in pixels. z: how dark the gaps between scanlines
+    // get. A vec4 keeps the uniform big enough for WebGL.
+    #[uniform(0)]
+    screen: Vec4,
+    #[texture(1)]
+    #[sampler(2)]
+    image: Handle<Image>
+}
+
+impl Material2d for CrtMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/crt.wgsl".into()
+    }
+}
+
+// A UI root that's kept over the game area instead of the whole window.
+#[derive(Component)]
+pub struct LetterboxedUi;
+
+pub struct DisplayPlugin;
+
+impl Plugin for DisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<CrtMaterial>::default())
+            .add_systems(Update, (
+                    setup_pixel_camera,
+                    update_layout,
+                    toggle_fullscreen,
+                    apply_fullscreen,
+                    apply_crt
+                ).chain()
+            );
+    }
+}
+
+// The largest whole scale that fits in the window, but never less than 1.
+pub fn pixel_scale(window: UVec2) -> u32 {
+    (window.x / GAME_SIZE.x).min(window.y / GAME_SIZE.y).max(1)
+}
+
+// The physical position and size of the game area. It's clipped to the
+// window when the window is smaller than the game.
+pub fn letterbox(window: UVec2) -> (UVec2, UVec2) {
+    let size = (GAME_SIZE * pixel_scale(window)).min(window);
+    ((window - size) / 2, size)
+}
+
+// The image the game is drawn into. Nearest filtering keeps its pixels
+// square when it's scaled up.
+fn game_image() -> Image {
+    let size = Extent3d {
+        width: GAME_SIZE.x,
+        height: GAME_SIZE.y,
+        ..default()
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("game_image"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[]
+        },
+        sampler: ImageSampler::nearest(),
+        ..default()
+    };
+    image.resize(size);
+    image
+}
+
+fn setup_pixel_camera(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<CrtMaterial>>,
+    mut cameras: Query<(Entity, &mut Camera), Added<PixelCamera>>
+) {
+    for (entity, mut camera) in &mut cameras {
+        let image = images.add(game_image());
+        camera.target = RenderTarget::Image(image.clone());
+        // Before anything that draws to the window.
+        camera.order = -2;
+
+        let quad = meshes.add(Mesh::from(shape::Quad::new(GAME_SIZE.as_vec2())));
+        let crt = materials.add(CrtMaterial {
+            screen: Vec4::new(GAME_SIZE.x as f32, GAME_SIZE.y as f32, 0.35, 0.0),
+            image: image.clone()
+        });
+        let visibility = |crt: bool| if crt == settings.crt { Visibility::Inherited } else { Visibility::Hidden };
+
+        // One camera clears the window, one draws the game image in the
+        // letterbox and one draws the UI over the whole window. They're all
+        // children of the pixel camera so they go away with it, and shaking
+        // it moves the game image and its camera together.
+        commands.entity(entity)
+            .insert(UiCameraConfig { show_ui: false })
+            .with_children(|parent| {
+                parent.spawn((
+                    Name::new("LetterboxClearCamera"),
+                    Camera2dBundle {
+                        camera: Camera {
+                            order: -1,
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    UiCameraConfig { show_ui: false },
+                    RenderLayers::layer(LETTERBOX_LAYER)
+                ));
+                parent.spawn((
+                    Name::new("UpscaleCamera"),
+                    UpscaleCamera,
+                    Camera2dBundle {
+                        camera_2d: Camera2d {
+                            clear_color: ClearColorConfig::None
+                        },
+                        projection: OrthographicProjection {
+                            scaling_mode: ScalingMode::Fixed {
+                                width: GAME_SIZE.x as f32,
+                                height: GAME_SIZE.y as f32
+                            },
+                            ..default()
+                        },
+                        transform: Transform::IDENTITY,
+                        ..default()
+                    },
+                    UiCameraConfig { show_ui: false },
+                    RenderLayers::layer(UPSCALE_LAYER)
+                ));
+                parent.spawn((
+                    Name::new("GameImage"),
+                    UpscaledGame { crt: false },
+                    SpriteBundle {
+                        sprite: Sprite {
+                            custom_size: Some(GAME_SIZE.as_vec2()),
+                            ..default()
+                        },
+                        texture: image.clone(),
+                        transform: Transform::from_xyz(0.0, 0.0, -10.0),
+                        visibility: visibility(false),
+                        ..default()
+                    },
+                    RenderLayers::layer(UPSCALE_LAYER)
+                ));
+                parent.spawn((
+                    Name::new("GameImageCrt"),
+                    UpscaledGame { crt: true },
+                    MaterialMesh2dBundle {
+                        mesh: quad.into(),
+                        material: crt,
+                        transform: Transform::from_xyz(0.0, 0.0, -10.0),
+                        visibility: visibility(true),
+                        ..default()
+                    },
+                    RenderLayers::layer(UPSCALE_LAYER)
+                ));
+                parent.spawn((
+                    Name::new("LetterboxUiCamera"),
+                    Camera2dBundle {
+                        camera: Camera {
+                            order: 1,
+                            ..default()
+                        },
+                        camera_2d: Camera2d {
+                            clear_color: ClearColorConfig::None
+                        },
+                        ..default()
+                    },
+                    RenderLayers::layer(LETTERBOX_LAYER)
+                ));
+            });
+    }
+}
+
+fn update_layout(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut ui_scale: ResMut<UiScale>,
+    mut cameras: Query<&mut Camera, With<UpscaleCamera>>,
+    mut roots: Query<&mut Style, With<LetterboxedUi>>
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let physical = UVec2::new(window.physical_width(), window.physical_height());
+    // Minimized.
+    if physical.x == 0 || physical.y == 0 {
+        return;
+    }
+    let scale = pixel_scale(physical);
+    let (position, size) = letterbox(physical);
+
+    for mut camera in &mut cameras {
+        let unchanged = camera.viewport.as_ref().is_some_and(|viewport| {
+            viewport.physical_position == position && viewport.physical_size == size
+        });
+        if !unchanged {
+            camera.viewport = Some(Viewport {
+                physical_position: position,
+                physical_size: size,
+                ..default()
+            });
+        }
+    }
+
+    let ui = scale as f64 / UI_UNITS_PER_PIXEL as f64 / window.scale_factor();
+    if ui_scale.0 != ui {
+        ui_scale.0 = ui;
+    }
+
+    // Physical pixels to UI units.
+    let units = |pixels: u32| Val::Px(pixels as f32 * UI_UNITS_PER_PIXEL / scale as f32);
+    let (left, top, width, height) = (units(position.x), units(position.y), units(size.x), units(size.y));
+    for mut style in &mut roots {
+        if style.left != left || style.top != top || style.width != width || style.height != height {
+            style.left = left;
+            style.top = top;
+            style.width = width;
+            style.height = height;
+        }
+    }
+}
+
+fn toggle_fullscreen(
+    keys: Res<Input<KeyCode>>,
+    mut settings: ResMut<Settings>
+) {
+    if keys.just_pressed(KeyCode::F11) {
+        settings.fullscreen = !settings.fullscreen;
+        settings.save();
+    }
+}
+
+fn apply_fullscreen(
+    settings: Res<Settings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let mode = if settings.fullscreen { WindowMode::BorderlessFullscreen } else { WindowMode::Windowed };
+    for mut window in &mut windows {
+        if window.mode != mode {
+            window.mode = mode;
+        }
+    }
+}
+
+fn apply_crt(
+    settings: Res<Settings>,
+    mut screens: Query<(&UpscaledGame, &mut Visibility)>
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for (screen, mut visibility) in &mut screens {
+        let shown = if screen.crt == settings.crt { Visibility::Inherited } else { Visibility::Hidden };
+        if *visibility != shown {
+            *visibility = shown;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::window::WindowResolution;
+
+    #[test]
+    fn scales_by_whole_numbers() {
+        assert_eq!(pixel_scale(UVec2::new(640, 480)), 2);
+        assert_eq!(pixel_scale(UVec2::new(1920, 1080)), 4);
+        assert_eq!(pixel_scale(UVec2::new(959, 2000)), 2);
+        assert_eq!(pixel_scale(UVec2::new(100, 100)), 1);
+    }
+
+    #[test]
+    fn centers_with_bars() {
+        assert_eq!(letterbox(UVec2::new(640, 480)), (UVec2::ZERO, UVec2::new(640, 480)));
+        assert_eq!(letterbox(UVec2::new(1920, 1080)), (UVec2::new(320, 60), UVec2::new(1280, 960)));
+        assert_eq!(letterbox(UVec2::new(700, 481)), (UVec2::new(30, 0), UVec2::new(640, 480)));
+        // Too small to fit, so the game is clipped rather than overflowing.
+        assert_eq!(letterbox(UVec2::new(200, 300)), (UVec2::new(0, 30), UVec2::new(200, 240)));
+    }
+
+    fn layout_app(resolution: WindowResolution) -> (App, Entity, Entity) {
+        let mut app = App::new();
+        app.insert_resource(UiScale(1.0))
+            .add_systems(Update, update_layout);
+        app.world.spawn((
+            Window {
+                resolution,
+                ..default()
+            },
+            PrimaryWindow
+        ));
+        let camera = app.world.spawn((Camera::default(), UpscaleCamera)).id();
+        let root = app.world.spawn((Style::default(), LetterboxedUi)).id();
+        app.update();
+        (app, camera, root)
+    }
+
+    #[test]
+    fn lays_out_camera_and_ui() {
+        let (app, camera, root) = layout_app(WindowResolution::new(1920.0, 1080.0));
+
+        let viewport = app.world.get::<Camera>(camera).unwrap().viewport.clone().unwrap();
+        assert_eq!(viewport.physical_position, UVec2::new(320, 60));
+        assert_eq!(viewport.physical_size, UVec2::new(1280, 960));
+
+        // The UI root always covers the game area in 640x480 units.
+        assert_eq!(app.world.resource::<UiScale>().0, 2.0);
+        let style = app.world.get::<Style>(root).unwrap();
+        assert_eq!(style.left, Val::Px(160.0));
+        assert_eq!(style.top, Val::Px(30.0));
+        assert_eq!(style.width, Val::Px(640.0));
+        assert_eq!(style.height, Val::Px(480.0));
+    }
+
+    #[test]
+    fn ui_scale_accounts_for_hidpi() {
+        // A 640x480 window on a 2x display.
+        let resolution = WindowResolution::new(1280.0, 960.0).with_scale_factor_override(2.0);
+        let (app, camera, root) = layout_app(resolution);
+
+        let viewport = app.world.get::<Camera>(camera).unwrap().viewport.clone().unwrap();
+        assert_eq!(viewport.physical_size, UVec2::new(1280, 960));
+        assert_eq!(app.world.resource::<UiScale>().0, 1.0);
+        assert_eq!(app.world.get::<Style>(root).unwrap().width, Val::Px(640.0));
+    }
+}
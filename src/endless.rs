@@ -0,0 +1,121 @@
+// Endless survival mode. The bear comes back stronger every time it's
+// beaten, snow and projectiles speed up the longer the run goes, and the
+// score is the seconds survived plus points for every hit.
+
+use bevy::prelude::*;
+
+// Seconds for the pace to go up by the base rate again, up to the cap.
+const RAMP_SECS: f32 = 60.0;
+const MAX_INTENSITY: f32 = 3.0;
+// Hits closer together than this keep the combo going.
+pub const COMBO_SECS: f32 = 2.0;
+const HIT_POINTS: u32 = 10;
+// Each time the bear comes back it's this much faster and throws this much
+// more often.
+pub const BEAR_SPEED_UP: f32 = 1.15;
+pub const BEAR_ATTACK_SPEED_UP: f32 = 1.15;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct EndlessRun {
+    pub seconds: f32,
+    pub hits: u32,
+    pub combo: u32,
+    pub peak_combo: u32,
+    // How many times the bear has been beaten.
+    pub bear_level: u32,
+    since_hit: f32
+}
+
+impl Default for EndlessRun {
+    fn default() -> Self {
+        Self {
+            seconds: 0.0,
+            hits: 0,
+            combo: 0,
+            peak_combo: 0,
+            bear_level: 0,
+            since_hit: f32::INFINITY
+        }
+    }
+}
+
+impl EndlessRun {
+    pub fn tick(&mut self, delta_secs: f32) {
+        self.seconds += delta_secs;
+        self.since_hit += delta_secs;
+        if self.since_hit > COMBO_SECS {
+            self.combo = 0;
+        }
+    }
+
+    pub fn hit(&mut self) {
+        self.hits += 1;
+        self.combo = if self.since_hit <= COMBO_SECS { self.combo + 1 } else { 1 };
+        self.peak_combo = self.peak_combo.max(self.combo);
+        self.since_hit = 0.0;
+    }
+
+    // Getting hurt breaks the combo.
+    pub fn hurt(&mut self) {
+        self.combo = 0;
+        self.since_hit = f32::INFINITY;
+    }
+
+    pub fn score(&self) -> u32 {
+        self.seconds as u32 + self.hits * HIT_POINTS
+    }
+
+    // Multiplies the snow and projectile rates.
+    pub fn intensity(&self) -> f32 {
+        (1.0 + self.seconds / RAMP_SECS).min(MAX_INTENSITY)
+    }
+}
+
+// Minutes and seconds, for the HUD and results.
+pub fn format_time(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combos_need_quick_hits_without_getting_hurt() {
+        let mut run = EndlessRun::default();
+        run.hit();
+        run.tick(1.0);
+        run.hit();
+        run.tick(1.0);
+        run.hit();
+        assert_eq!(run.combo, 3);
+
+        // Too slow.
+        run.tick(COMBO_SECS + 0.1);
+        assert_eq!(run.combo, 0);
+        run.hit();
+        assert_eq!(run.combo, 1);
+
+        run.hit();
+        run.hurt();
+        run.hit();
+        assert_eq!(run.combo, 1);
+        assert_eq!(run.peak_combo, 3);
+        assert_eq!(run.hits, 6);
+    }
+
+    #[test]
+    fn scores_time_and_hits_and_ramps_up() {
+        let mut run = EndlessRun::default();
+        assert_eq!(run.intensity(), 1.0);
+        run.tick(90.0);
+        run.hit();
+        assert_eq!(run.score(), 90 + HIT_POINTS);
+        assert_eq!(run.intensity(), 2.5);
+        assert_eq!(format_time(run.seconds), "1:30");
+
+        run.tick(RAMP_SECS * 10.0);
+        assert_eq!(run.intensity(), MAX_INTENSITY);
+    }
+}
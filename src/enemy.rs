@@ -0,0 +1,195 @@
+use bevy::prelude::*;
+
+// Enemy roster data. Everything that makes one enemy different from another
+// lives here so new enemies can be added without touching the game systems.
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub enum EnemyKind {
+    SkyBear,
+    Minion,
+    Walker,
+    Shielded
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Reflect)]
+pub enum EnemyMovement {
+    // Bounce back and forth between two x bounds.
+    Patrol { min_x: f32, max_x: f32 },
+    // Fly straight across the screen once and leave.
+    FlyAcross
+}
+
+pub struct EnemyArchetype {
+    pub name: &'static str,
+    pub sprite: &'static str,
+    pub tint: Color,
+    pub scale: f32,
+    pub radius: f32,
+    pub spawn_y: f32,
+    pub health: f32,
+    pub speed: f32,
+    pub movement: EnemyMovement,
+    // Only the sky bear spins while it moves.
+    pub spins: bool,
+    // Seconds between projectiles, or None if the enemy never attacks.
+    pub attack_interval: Option<f32>,
+    // Shield points that soak up damage before health is touched.
+    pub shield: f32,
+    // Walkers are solid so snow bumps into them instead of passing through.
+    pub blocks_snow: bool,
+    // The match is won when every boss is beaten.
+    pub boss: bool
+}
+
+impl EnemyKind {
+    pub fn archetype(self) -> EnemyArchetype {
+        match self {
+            EnemyKind::SkyBear => EnemyArchetype {
+                name: "EnemyEntity",
+                sprite: "enemy.png",
+                tint: Color::WHITE,
+                scale: 2.0,
+                radius: 8.0,
+                spawn_y: 96.0,
+                health: 100.0,
+                speed: 40.0,
+                movement: EnemyMovement::Patrol { min_x: -130.0, max_x: 130.0 },
+                spins: true,
+                attack_interval: Some(0.25),
+                shield: 0.0,
+                blocks_snow: false,
+                boss: true
+            },
+            EnemyKind::Minion => EnemyArchetype {
+                name: "MinionEntity",
+                sprite: "enemy.png",
+                tint: Color::rgb(1.0, 0.7, 0.7),
+                scale: 1.0,
+                radius: 8.0,
+                spawn_y: 56.0,
+                health: 6.0,
+                speed: 30.0,
+                movement: EnemyMovement::FlyAcross,
+                spins: false,
+                attack_interval: Some(1.5),
+                shield: 0.0,
+                blocks_snow: false,
+                boss: false
+            },
+            EnemyKind::Walker => EnemyArchetype {
+                name: "WalkerEntity",
+                sprite: "enemy.png",
+                tint: Color::rgb(0.6, 0.8, 0.6),
+                scale: 1.5,
+                radius: 8.0,
+                spawn_y: -76.0,
+                health: 15.0,
+                speed: 20.0,
+                movement: EnemyMovement::Patrol { min_x: -100.0, max_x: 100.0 },
+                spins: false,
+                attack_interval: None,
+                shield: 0.0,
+                blocks_snow: true,
+                boss: false
+            },
+            EnemyKind::Shielded => EnemyArchetype {
+                name: "ShieldedEntity",
+                sprite: "enemy.png",
+                tint: Color::rgb(0.6, 0.7, 1.0),
+                scale: 1.5,
+                radius: 8.0,
+                spawn_y: 40.0,
+                health: 20.0,
+                speed: 30.0,
+                movement: EnemyMovement::Patrol { min_x: -110.0, max_x: 110.0 },
+                spins: false,
+                attack_interval: Some(1.0),
+                shield: 15.0,
+                blocks_snow: false,
+                boss: false
+            }
+        }
+    }
+}
+
+pub struct RosterEntry {
+    pub kind: EnemyKind,
+    pub x: f32,
+    // Spawn again every N seconds after the first spawn.
+    pub repeat_secs: Option<f32>,
+    next_secs: Option<f32>
+}
+
+impl RosterEntry {
+    // Spawn `kind` at `x` once the match is `at_secs` seconds old.
+    pub fn new(kind: EnemyKind, x: f32, at_secs: f32) -> Self {
+        Self {
+            kind,
+            x,
+            repeat_secs: None,
+            next_secs: Some(at_secs)
+        }
+    }
+
+    pub fn repeating(mut self, repeat_secs: f32) -> Self {
+        self.repeat_secs = Some(repeat_secs);
+        self
+    }
+}
+
+#[derive(Resource)]
+pub struct EnemyRoster {
+    pub entries: Vec<RosterEntry>,
+    pub elapsed: f32
+}
+
+impl Default for EnemyRoster {
+    fn default() -> Self {
+        Self {
+            entries: vec![
+                RosterEntry::new(EnemyKind::SkyBear, 64.0, 0.0),
+                RosterEntry::new(EnemyKind::Minion, -200.0, 8.0).repeating(12.0),
+                RosterEntry::new(EnemyKind::Walker, 100.0, 15.0),
+                RosterEntry::new(EnemyKind::Minion, 200.0, 20.0).repeating(12.0),
+                RosterEntry::new(EnemyKind::Shielded, -100.0, 30.0),
+            ],
+            elapsed: 0.0
+        }
+    }
+}
+
+impl EnemyRoster {
+    // A roster that never spawns anything, for matches without enemies.
+    pub fn empty() -> Self {
+        Self {
+            entries: Vec::new(),
+            elapsed: 0.0
+        }
+    }
+
+    // Just the sky bear, for time attack.
+    pub fn boss_only() -> Self {
+        Self {
+            entries: vec![RosterEntry::new(EnemyKind::SkyBear, 64.0, 0.0)],
+            elapsed: 0.0
+        }
+    }
+
+    // Advance the roster clock and return every (kind, x) that is due to spawn.
+    pub fn tick(&mut self, delta_secs: f32) -> Vec<(EnemyKind, f32)> {
+        self.elapsed += delta_secs;
+        let mut due = Vec::new();
+        for entry in self.entries.iter_mut() {
+            while let Some(next) = entry.next_secs {
+                if next > self.elapsed {
+                    break;
+                }
+                due.push((entry.kind, entry.x));
+                entry.next_secs = entry.repeat_secs
+                    .filter(|repeat| *repeat > 0.0)
+                    .map(|repeat| next + repeat);
+            }
+        }
+        due
+    }
+}
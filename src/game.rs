@@ -1,51 +1,116 @@
 use bevy::prelude::*;
+use bevy::app::AppExit;
+use bevy::ecs::schedule::{ScheduleLabel, SystemConfigs};
 use bevy_inspector_egui::prelude::*;
-use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy::render::camera::ScalingMode;
+use bevy::time::TimeUpdateStrategy;
 use bevy_scroller::{
     Scroller, ScrollerBundle, ScrollerDirection, ScrollerPlugin, ScrollerSize, SingleSpriteGenerator
 };
 use bevy_tweening::{lens::*, *};
 use bevy_xpbd_2d::{math::*, prelude::*};
-use rand::Rng;
-use std::collections::VecDeque;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
 
+use crate::camerafx::*;
+use crate::cli::Cli;
+use crate::console::*;
+use crate::damagefx::{DamageEvent, HitFlash};
+use crate::difficulty::{AdaptiveDifficulty, Difficulty};
+use crate::display::{LetterboxedUi, PixelCamera};
+use crate::endless::{self, EndlessRun, BEAR_ATTACK_SPEED_UP, BEAR_SPEED_UP};
+use crate::enemy::*;
+use crate::net::*;
+use crate::leaderboard::{Leaderboard, LeaderboardCategory, LeaderboardEntry};
+use crate::particles::*;
 use crate::progressbar::*;
+use crate::replay::{Replay, ReplaySettings};
+use crate::settings::Settings;
+use crate::stats::MatchStats;
+use crate::timeattack::{self, PersonalBestKey, PersonalBests, TimeAttackRun, SPLIT_MARKS};
 
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
+        // Systems here and in the console read the launch options, so put
+        // in the defaults if the app was built without any.
+        if !app.world.contains_resource::<Cli>() {
+            app.insert_resource(Cli::default());
+        }
+        let cli = app.world.resource::<Cli>().clone();
+
         // Plugins.
         app.add_plugins((
-            PhysicsPlugins::default(),
+            // Physics runs in its own schedule so netplay can step it by hand.
+            PhysicsPlugins::new(PhysicsUpdate),
             ScrollerPlugin,
-            TweeningPlugin,
-            // Debugging...
-            //PhysicsDebugPlugin::default(),
-            //WorldInspectorPlugin::new()
+            TweeningPlugin
         ));
 
-        // App state.
+        // App state, starting wherever the command line asked for. Replays
+        // go straight into the match unless told otherwise.
+        let replay_state = cli.replay.is_some().then_some(AppState::InGame);
+        app.insert_resource(State::new(cli.state.or(replay_state).unwrap_or_default()));
         app.add_state::<AppState>();
+        app.init_resource::<GameMode>();
+        app.insert_resource(VersusMatch::new(3));
+        app.insert_resource(cli.difficulty.unwrap_or_default());
+        app.init_resource::<AdaptiveDifficulty>();
+        // A replay starts out on the settings it was recorded with. Picking
+        // others on the menu gets it refused, see setup_replay.
+        if let Some(Ok(replay)) = cli.replay.as_deref().map(Replay::load) {
+            app.insert_resource(replay.settings.mode);
+            app.insert_resource(replay.settings.difficulty);
+            let mut adaptive = AdaptiveDifficulty::default();
+            adaptive.enabled = replay.settings.adaptive;
+            app.insert_resource(adaptive);
+        }
+        app.insert_resource(PersonalBests::load());
+        app.init_resource::<GodMode>();
+        app.add_systems(Update, (
+                apply_console_commands,
+                spawn_from_console
+            )
+        );
+
+        // Playing back doesn't need to keep up with real time, so every
+        // frame is one fixed step. Recording keeps real time, see
+        // replay_step.
+        if cli.replay.is_some() || cli.headless {
+            app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(NET_DT)));
+        }
+        // Nobody is there to close a headless run, see exit_headless.
+        if cli.headless {
+            app.add_systems(Update, exit_headless);
+        }
 
         // Debug types.
         app.register_type::<Speed>();
         app.register_type::<EnemyDirection>();
         app.register_type::<EnemyHealth>();
+        app.register_type::<EnemyShield>();
+        app.register_type::<EnemyKind>();
+        app.register_type::<EnemyMovement>();
+        app.register_type::<SnowConfig>();
+        app.register_type::<ProjectileConfig>();
+
+        // Health bars follow these.
+        app.add_progress_source::<PlayerHealth>();
+        app.add_progress_source::<EnemyHealth>();
 
         // Resources.
         app.insert_resource(ClearColor(Color::rgb(0.0, 0.0, 0.0)));
         app.insert_resource(Gravity(Vector::NEG_Y * 100.0 * 10.0));
 
-        app.add_systems(Startup, spawn_health_bar);
-
         // MainMenu state sytems.
         app.add_systems(OnEnter(AppState::MainMenu),
             setup_main_menu
         );
         app.add_systems(Update, (
                 action_main_menu,
+                action_difficulty,
                 button_main_menu
             ).run_if(in_state(AppState::MainMenu))
         );
@@ -66,38 +131,89 @@ impl Plugin for GamePlugin {
             despawn_screen::<OnCreditsScreen>
         );
 
+        // Settings state systems.
+        app.add_systems(OnEnter(AppState::Settings),
+            setup_settings
+        );
+        app.add_systems(Update, (
+                action_settings,
+                action_credits,
+                button_credits
+            ).run_if(in_state(AppState::Settings))
+        );
+        app.add_systems(OnExit(AppState::Settings),
+            despawn_screen::<OnSettingsScreen>
+        );
+
         // InGame state systems.
         app.add_systems(OnEnter(AppState::InGame),(
                 setup_game,
+                setup_time_attack.run_if(resource_equals(GameMode::TimeAttack)),
                 setup_snow_and_projectiles,
-                reset_player_health_bar,
-                reset_enemy_health_bar
+                setup_replay,
+                setup_netplay
             ).chain()
         );
         app.add_systems(Update, (
+                read_player_input,
                 anim_snow_fx,
-                anim_enemy,
-                move_enemy,
+                (
+                    spawn_roster_enemies,
+                    anim_enemy,
+                    move_enemy
+                ).chain().run_if(not(resource_exists::<ReplayClock>())),
                 anim_player,
-                move_player,
-                spawn_snow,
-                move_snow,
-                spawn_enemy_projectiles,
-                move_enemy_projectiles,
-                collide_snow_with_player,
-                collide_snow_with_enemy,
-                collide_projectile_with_player,
-                update_enemy_health_bar,
-                update_player_health_bar,
-                remove_snow,
-                remove_enemy_projectiles
+                play_hit_sounds,
+                simulation_systems().run_if(stepped_each_frame),
+                netplay_step.run_if(resource_exists::<Netplay>()),
+                replay_step.run_if(resource_exists::<ReplayClock>()),
+                (
+                    endless_systems().run_if(not(resource_exists::<ReplayClock>())),
+                    update_endless_hud
+                ).chain().run_if(resource_equals(GameMode::Endless)),
+                (
+                    track_time_attack,
+                    update_time_attack_hud,
+                    move_ghost
+                ).chain().run_if(resource_equals(GameMode::TimeAttack)),
+                tint_players
             ).chain()
+            .before(ProgressBarSet::Bind)
             .run_if(in_state(AppState::InGame))
         );
+        app.add_systems(Update, (
+                (
+                    win_on_boss_bars_emptied,
+                    end_on_player_bars_emptied
+                ).run_if(not(resource_exists::<Netplay>())),
+                end_round_on_confirmed_health.run_if(resource_exists::<Netplay>()),
+                camera_fx_on_damage,
+                adapt_difficulty.run_if(stepped_each_frame)
+            ).after(ProgressBarSet::Notify)
+            .run_if(in_state(AppState::InGame))
+        );
+        app.add_systems(NetplayUpdate, simulation_systems());
+        // Everything that moves the match on, stepped by replay_step.
+        app.add_systems(ReplayUpdate, (
+                replay_input,
+                spawn_roster_enemies,
+                anim_enemy,
+                move_enemy,
+                simulation_systems(),
+                endless_systems().run_if(resource_equals(GameMode::Endless)),
+                adapt_difficulty
+            ).chain()
+        );
+        app.add_systems(PostUpdate,
+            run_physics
+                .run_if(stepped_each_frame)
+                .before(bevy::transform::TransformSystem::TransformPropagate)
+        );
         app.add_systems(OnExit(AppState::InGame), (
-                hide_player_health_bar,
-                hide_enemy_health_bar,
-                despawn_screen::<OnInGameScreen>
+                despawn_screen::<OnInGameScreen>,
+                clear_particles,
+                stop_netplay,
+                finish_replay
             )
         );
 
@@ -110,8 +226,10 @@ impl Plugin for GamePlugin {
                 button_credits
             ).run_if(in_state(AppState::Win))
         );
-        app.add_systems(OnExit(AppState::Win),
-            despawn_screen::<OnWinGameScreen>
+        app.add_systems(OnExit(AppState::Win), (
+                despawn_screen::<OnWinGameScreen>,
+                clear_particles
+            )
         );
         // Lose state systems.
         app.add_systems(OnEnter(AppState::Lose),
@@ -122,20 +240,152 @@ impl Plugin for GamePlugin {
                 button_credits
             ).run_if(in_state(AppState::Lose))
         );
-        app.add_systems(OnExit(AppState::Lose),
-            despawn_screen::<OnLoseGameScreen>
+        app.add_systems(OnExit(AppState::Lose), (
+                despawn_screen::<OnLoseGameScreen>,
+                clear_particles
+            )
+        );
+        // RoundResults state systems.
+        app.add_systems(OnEnter(AppState::RoundResults),
+            setup_round_results_screen
+        );
+        app.add_systems(Update, (
+                action_credits,
+                button_credits
+            ).run_if(in_state(AppState::RoundResults))
+        );
+        app.add_systems(OnExit(AppState::RoundResults), (
+                despawn_screen::<OnRoundResultsScreen>,
+                clear_particles
+            )
+        );
+        // EndlessResults state systems.
+        app.add_systems(OnEnter(AppState::EndlessResults),
+            setup_endless_results_screen
+        );
+        app.add_systems(Update, (
+                action_credits,
+                button_credits
+            ).run_if(in_state(AppState::EndlessResults))
+        );
+        app.add_systems(OnExit(AppState::EndlessResults), (
+                despawn_screen::<OnEndlessResultsScreen>,
+                clear_particles
+            )
         );
     }
 }
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
-enum AppState {
+pub enum AppState {
     #[default]
     MainMenu,
     Credits,
+    Settings,
     InGame,
     Win,
-    Lose
+    Lose,
+    RoundResults,
+    EndlessResults
+}
+
+impl AppState {
+    // For --state on the command line.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "main-menu" | "menu" => Some(AppState::MainMenu),
+            "credits" => Some(AppState::Credits),
+            "settings" => Some(AppState::Settings),
+            "game" | "in-game" => Some(AppState::InGame),
+            "win" => Some(AppState::Win),
+            "lose" => Some(AppState::Lose),
+            "results" | "round-results" => Some(AppState::RoundResults),
+            "endless-results" => Some(AppState::EndlessResults),
+            _ => None
+        }
+    }
+}
+
+// Which kind of match to set up when entering InGame.
+#[derive(Resource, Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum GameMode {
+    #[default]
+    Single,
+    Coop,
+    Versus,
+    // The bear keeps coming back, see endless.rs.
+    Endless,
+    // Beat the bear against the clock, see timeattack.rs.
+    TimeAttack
+}
+
+impl GameMode {
+    // For replay files.
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(GameMode::Single),
+            1 => Some(GameMode::Coop),
+            2 => Some(GameMode::Versus),
+            3 => Some(GameMode::Endless),
+            4 => Some(GameMode::TimeAttack),
+            _ => None
+        }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        match self {
+            GameMode::Single => 0,
+            GameMode::Coop => 1,
+            GameMode::Versus => 2,
+            GameMode::Endless => 3,
+            GameMode::TimeAttack => 4
+        }
+    }
+}
+
+// Score keeping for a best-of-N versus match.
+#[derive(Resource)]
+struct VersusMatch {
+    best_of: u32,
+    wins: [u32; 2],
+    round: u32,
+    // None after a drawn round.
+    last_winner: Option<usize>,
+    // Played against the peer given on the command line.
+    online: bool
+}
+
+impl VersusMatch {
+    fn new(best_of: u32) -> Self {
+        Self {
+            best_of,
+            wins: [0, 0],
+            round: 1,
+            last_winner: None,
+            online: false
+        }
+    }
+
+    // Ends the round given which slots are down. Both going down on the
+    // same frame is a draw, and neither gets the win.
+    fn finish_round(&mut self, down: [bool; 2]) -> bool {
+        self.last_winner = match down {
+            [true, true] => None,
+            [true, false] => Some(1),
+            [false, true] => Some(0),
+            [false, false] => return false
+        };
+        if let Some(winner) = self.last_winner {
+            self.wins[winner] += 1;
+        }
+        true
+    }
+
+    // The slot that has won more than half of the rounds, if any.
+    fn match_winner(&self) -> Option<usize> {
+        let needed = self.best_of / 2 + 1;
+        self.wins.iter().position(|wins| *wins >= needed)
+    }
 }
 
 // MainMenu data and functions...
@@ -154,6 +404,9 @@ struct OnMainMenuScreen;
 #[derive(Component)]
 struct OnCreditsScreen;
 
+#[derive(Component)]
+struct OnSettingsScreen;
+
 #[derive(Component)]
 struct OnInGameScreen;
 
@@ -163,6 +416,12 @@ struct OnWinGameScreen;
 #[derive(Component)]
 struct OnLoseGameScreen;
 
+#[derive(Component)]
+struct OnRoundResultsScreen;
+
+#[derive(Component)]
+struct OnEndlessResultsScreen;
+
 // Tag to mark the selected button.
 #[derive(Component)]
 struct SelectedButton;
@@ -171,11 +430,35 @@ struct SelectedButton;
 #[derive(Component)]
 enum MainMenuButtonActions {
     Start,
+    TwoPlayers,
+    Versus,
+    Endless,
+    TimeAttack,
+    Settings,
     Credits
 }
+
+// The difficulty buttons on the main menu cycle through their choices.
+#[derive(Component, Clone, Copy)]
+enum DifficultyButtonActions {
+    Preset,
+    Adaptive
+}
+
+impl DifficultyButtonActions {
+    fn label(self, difficulty: Difficulty, adaptive: &AdaptiveDifficulty) -> String {
+        match self {
+            DifficultyButtonActions::Preset => difficulty.name().into(),
+            DifficultyButtonActions::Adaptive => format!("Adaptive: {}", if adaptive.enabled { "On" } else { "Off" })
+        }
+    }
+}
+
 #[derive(Component)]
 enum OtherButtonActions {
-    Back
+    Back,
+    NextRound,
+    PlayAgain
 }
 
 #[derive(Component)]
@@ -205,7 +488,10 @@ struct IceWasHitSound;
 
 fn setup_main_menu(
     mut commands: Commands,
-    asset_server: Res<AssetServer>
+    asset_server: Res<AssetServer>,
+    net_config: Option<Res<NetConfig>>,
+    difficulty: Res<Difficulty>,
+    adaptive: Res<AdaptiveDifficulty>
 ) {
     // Start music.
     commands.spawn((
@@ -222,17 +508,17 @@ fn setup_main_menu(
         MainMenuMusic,
     ));
 
-    // Define the base button styles.
+    // Define the base button styles. Kept short so every mode fits.
     let button_style = Style {
         width: Val::Px(250.0),
-        height: Val::Px(65.0),
-        margin: UiRect::all(Val::Px(20.0)),
+        height: Val::Px(40.0),
+        margin: UiRect::all(Val::Px(3.0)),
         justify_content: JustifyContent::Center,
         align_items: AlignItems::Center,
         ..default()
     };
     let button_text_style = TextStyle {
-        font_size: 40.0,
+        font_size: 32.0,
         color: TEXT_COLOR,
         ..default()
     };
@@ -256,7 +542,10 @@ fn setup_main_menu(
         // Title text.
         parent.spawn(TextBundle::from_section(
             "Lots of Snow",
-            button_text_style.clone()
+            TextStyle {
+                font_size: 40.0,
+                ..button_text_style.clone()
+            }
         ));
         // Start button.
         parent.spawn((
@@ -273,6 +562,125 @@ fn setup_main_menu(
                 button_text_style.clone()
             ));
         });
+        // Co-op button.
+        parent.spawn((
+            ButtonBundle {
+                style: button_style.clone(),
+                background_color: NORMAL_BUTTON.into(),
+                ..default()
+            },
+            MainMenuButtonActions::TwoPlayers
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "2 Players",
+                button_text_style.clone()
+            ));
+        });
+        // Versus button. Plays online when a peer was given on the command line.
+        parent.spawn((
+            ButtonBundle {
+                style: button_style.clone(),
+                background_color: NORMAL_BUTTON.into(),
+                ..default()
+            },
+            MainMenuButtonActions::Versus
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                if net_config.is_some() { "Online" } else { "Versus" },
+                button_text_style.clone()
+            ));
+        });
+        // Solo challenge buttons, side by side.
+        parent.spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(262.0),
+                justify_content: JustifyContent::SpaceBetween,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for (action, label) in [
+                (MainMenuButtonActions::Endless, "Endless"),
+                (MainMenuButtonActions::TimeAttack, "Time Attack")
+            ] {
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(122.0),
+                            ..button_style.clone()
+                        },
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    },
+                    action
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        label,
+                        TextStyle {
+                            font_size: 22.0,
+                            ..button_text_style.clone()
+                        }
+                    ));
+                });
+            }
+        });
+        // Difficulty buttons, side by side.
+        parent.spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(262.0),
+                justify_content: JustifyContent::SpaceBetween,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            for action in [DifficultyButtonActions::Preset, DifficultyButtonActions::Adaptive] {
+                parent.spawn((
+                    ButtonBundle {
+                        style: Style {
+                            width: Val::Px(122.0),
+                            height: Val::Px(32.0),
+                            margin: UiRect::vertical(Val::Px(3.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    },
+                    action
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        action.label(*difficulty, &adaptive),
+                        TextStyle {
+                            font_size: 20.0,
+                            color: TEXT_COLOR,
+                            ..default()
+                        }
+                    ));
+                });
+            }
+        });
+        // Settings button.
+        parent.spawn((
+            ButtonBundle {
+                style: button_style.clone(),
+                background_color: NORMAL_BUTTON.into(),
+                ..default()
+            },
+            MainMenuButtonActions::Settings
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Settings",
+                button_text_style.clone()
+            ));
+        });
         // Credits button.
         parent.spawn((
             ButtonBundle {
@@ -291,7 +699,8 @@ fn setup_main_menu(
         // Instructions text.
         parent.spawn(
             TextBundle::from_section(
-                "Instructions:\nLaunch the ice blocks into the evil sky bear! (WASD + SpaceBar)\nDon't get hit by his teddy toys!",
+                "Instructions:\nLaunch the ice blocks into the evil sky bear! (WASD + SpaceBar)\nDon't get hit by his teddy toys!\n\
+                2 Players: P2 uses Arrows + Enter or a gamepad.\nHold launch next to a downed partner to revive them!",
                 TextStyle {
                     font_size: 16.0,
                     color: Color::rgb(1.0, 1.0, 1.0),
@@ -301,7 +710,7 @@ fn setup_main_menu(
             .with_text_alignment(TextAlignment::Center)
             .with_style(Style {
                 position_type: PositionType::Relative,
-                bottom: Val::Px(-45.0),
+                bottom: Val::Px(-20.0),
                 //right: Val::Px(497.0),
                 ..default()
             })
@@ -313,14 +722,41 @@ fn setup_main_menu(
 
 fn action_main_menu(
     interaction_query: Query<(&Interaction, &MainMenuButtonActions), (Changed<Interaction>, With<Button>)>,
-    mut app_state: ResMut<NextState<AppState>>
+    mut app_state: ResMut<NextState<AppState>>,
+    mut game_mode: ResMut<GameMode>,
+    mut versus: ResMut<VersusMatch>,
+    net_config: Option<Res<NetConfig>>
 ) {
     for (interaction, button_action) in &interaction_query {
         if *interaction == Interaction::Pressed {
             match button_action {
                 MainMenuButtonActions::Start => {
+                    *game_mode = GameMode::Single;
+                    app_state.set(AppState::InGame);
+                },
+                MainMenuButtonActions::TwoPlayers => {
+                    *game_mode = GameMode::Coop;
                     app_state.set(AppState::InGame);
                 },
+                MainMenuButtonActions::Versus => {
+                    *game_mode = GameMode::Versus;
+                    *versus = VersusMatch {
+                        online: net_config.is_some(),
+                        ..VersusMatch::new(3)
+                    };
+                    app_state.set(AppState::InGame);
+                },
+                MainMenuButtonActions::Endless => {
+                    *game_mode = GameMode::Endless;
+                    app_state.set(AppState::InGame);
+                },
+                MainMenuButtonActions::TimeAttack => {
+                    *game_mode = GameMode::TimeAttack;
+                    app_state.set(AppState::InGame);
+                },
+                MainMenuButtonActions::Settings => {
+                    app_state.set(AppState::Settings);
+                },
                 MainMenuButtonActions::Credits => {
                     app_state.set(AppState::Credits);
                 }
@@ -329,6 +765,28 @@ fn action_main_menu(
     }
 }
 
+fn action_difficulty(
+    interaction_query: Query<(&Interaction, &DifficultyButtonActions, &Children), (Changed<Interaction>, With<Button>)>,
+    mut texts: Query<&mut Text>,
+    mut difficulty: ResMut<Difficulty>,
+    mut adaptive: ResMut<AdaptiveDifficulty>
+) {
+    for (interaction, button_action, children) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            match button_action {
+                DifficultyButtonActions::Preset => *difficulty = difficulty.next(),
+                DifficultyButtonActions::Adaptive => adaptive.enabled = !adaptive.enabled
+            }
+
+            // Show the new choice on the button.
+            let mut button_texts = texts.iter_many_mut(children);
+            while let Some(mut text) = button_texts.fetch_next() {
+                text.sections[0].value = button_action.label(*difficulty, &adaptive);
+            }
+        }
+    }
+}
+
 fn action_credits(
     interaction_query: Query<(&Interaction, &OtherButtonActions), (Changed<Interaction>, With<Button>)>,
     mut app_state: ResMut<NextState<AppState>>
@@ -338,6 +796,9 @@ fn action_credits(
             match button_action {
                 OtherButtonActions::Back => {
                     app_state.set(AppState::MainMenu);
+                },
+                OtherButtonActions::NextRound | OtherButtonActions::PlayAgain => {
+                    app_state.set(AppState::InGame);
                 }
             }
         }
@@ -466,6 +927,150 @@ fn setup_credits(
     commands.spawn(Camera2dBundle::default()).insert(OnCreditsScreen);
 }
 
+// Settings data and functions...
+
+// Each button cycles through the choices for one setting.
+#[derive(Component, Clone, Copy)]
+enum SettingsButtonActions {
+    ScreenShake,
+    HitStop,
+    Fullscreen,
+    Crt
+}
+
+impl SettingsButtonActions {
+    fn label(self, settings: &Settings) -> String {
+        match self {
+            SettingsButtonActions::ScreenShake if settings.screen_shake <= 0.0 => "Screen Shake: Off".into(),
+            SettingsButtonActions::ScreenShake => format!("Screen Shake: {:.0}%", settings.screen_shake * 100.0),
+            SettingsButtonActions::HitStop => format!("Hit-Stop: {}", if settings.hit_stop { "On" } else { "Off" }),
+            SettingsButtonActions::Fullscreen => format!("Fullscreen: {}", if settings.fullscreen { "On" } else { "Off" }),
+            SettingsButtonActions::Crt => format!("CRT Filter: {}", if settings.crt { "On" } else { "Off" })
+        }
+    }
+
+    fn cycle(self, settings: &mut Settings) {
+        match self {
+            SettingsButtonActions::ScreenShake => {
+                settings.screen_shake = if settings.screen_shake > 0.5 {
+                    0.5
+                } else if settings.screen_shake > 0.0 {
+                    0.0
+                } else {
+                    1.0
+                };
+            },
+            SettingsButtonActions::HitStop => settings.hit_stop = !settings.hit_stop,
+            SettingsButtonActions::Fullscreen => settings.fullscreen = !settings.fullscreen,
+            SettingsButtonActions::Crt => settings.crt = !settings.crt
+        }
+    }
+}
+
+fn setup_settings(
+    mut commands: Commands,
+    settings: Res<Settings>
+) {
+    // Define the base button styles.
+    let button_style = Style {
+        width: Val::Px(400.0),
+        height: Val::Px(55.0),
+        margin: UiRect::all(Val::Px(10.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = TextStyle {
+        font_size: 32.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+
+    // Set up the button layout using nodes.
+    commands.spawn((
+        OnSettingsScreen,
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        }
+    ))
+    .with_children(|parent| {
+        // Title text.
+        parent.spawn(TextBundle::from_section(
+            "Settings",
+            TextStyle {
+                font_size: 40.0,
+                color: TEXT_COLOR,
+                ..default()
+            }
+        ));
+        // One button per setting.
+        for action in [
+            SettingsButtonActions::ScreenShake,
+            SettingsButtonActions::HitStop,
+            SettingsButtonActions::Fullscreen,
+            SettingsButtonActions::Crt
+        ] {
+            parent.spawn((
+                ButtonBundle {
+                    style: button_style.clone(),
+                    background_color: NORMAL_BUTTON.into(),
+                    ..default()
+                },
+                action
+            ))
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    action.label(&settings),
+                    button_text_style.clone()
+                ));
+            });
+        }
+        // Back button.
+        parent.spawn((
+            ButtonBundle {
+                style: button_style.clone(),
+                background_color: NORMAL_BUTTON.into(),
+                ..default()
+            },
+            OtherButtonActions::Back,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Back",
+                button_text_style.clone()
+            ));
+        });
+    });
+    // Create camera to view the menu.
+    commands.spawn(Camera2dBundle::default()).insert(OnSettingsScreen);
+}
+
+fn action_settings(
+    interaction_query: Query<(&Interaction, &SettingsButtonActions, &Children), (Changed<Interaction>, With<Button>)>,
+    mut texts: Query<&mut Text>,
+    mut settings: ResMut<Settings>
+) {
+    for (interaction, button_action, children) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            button_action.cycle(&mut settings);
+            settings.save();
+
+            // Show the new choice on the button.
+            let mut button_texts = texts.iter_many_mut(children);
+            while let Some(mut text) = button_texts.fetch_next() {
+                text.sections[0].value = button_action.label(&settings);
+            }
+        }
+    }
+}
+
 // InGame data and functions...
 
 #[derive(Resource)]
@@ -481,22 +1086,160 @@ struct PlayerSprite;
 #[reflect(InspectorOptions)]
 struct PlayerHealth (f32);
 
-#[derive(Component)]
-struct EnemyCapsule;
+impl ProgressSource for PlayerHealth {
+    fn progress(&self) -> f32 {
+        self.0
+    }
+}
 
+// Base sprite color so players can tell each other apart.
 #[derive(Component)]
-struct EnemyProjectile;
+struct PlayerTint(Color);
 
-#[derive(Component, Reflect, InspectorOptions)]
-#[reflect(InspectorOptions)]
-struct EnemyHealth (f32);
+// 0 for player one, 1 for player two.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+struct PlayerSlot(usize);
 
-#[derive(Component, Reflect, InspectorOptions)]
-#[reflect(InspectorOptions)]
-struct EnemyDirection(f32);
+// Which way the player launches snow: 1.0 is right, -1.0 is left.
+#[derive(Component)]
+struct PlayerFacing(f32);
+
+// Everything that differs between spawned players.
+struct PlayerSetup {
+    name: &'static str,
+    slot: usize,
+    x: f32,
+    facing: f32,
+    bindings: PlayerBindings,
+    tint: Color,
+    // The HUD column the health bar goes in.
+    hud: Entity,
+    label: (&'static str, Color),
+    bar_direction: FillDirection
+}
+
+// Added when a player runs out of health. A partner holding launch
+// nearby fills up `revive` until the player gets back up.
+#[derive(Component, Default)]
+struct Downed {
+    revive: f32
+}
+
+// This frame's input for one player, read from its bindings.
+#[derive(Component, Default, Clone, Copy, PartialEq)]
+struct PlayerInput {
+    left: bool,
+    right: bool,
+    action: bool
+}
+
+#[derive(Component)]
+struct PlayerBindings {
+    left: Vec<KeyCode>,
+    right: Vec<KeyCode>,
+    action: Vec<KeyCode>,
+    gamepad: Option<Gamepad>
+}
+
+impl PlayerBindings {
+    // Every key on the keyboard when playing alone.
+    fn single() -> Self {
+        Self {
+            left: vec![KeyCode::A, KeyCode::Left],
+            right: vec![KeyCode::D, KeyCode::Right],
+            action: vec![
+                KeyCode::Space,
+                KeyCode::Z, KeyCode::X, KeyCode::C,
+                KeyCode::Return,
+                KeyCode::Tab,
+                KeyCode::ShiftLeft,
+                KeyCode::ShiftRight],
+            gamepad: Some(Gamepad::new(0))
+        }
+    }
+
+    // Left half of the keyboard for player one in co-op.
+    fn coop_one() -> Self {
+        Self {
+            left: vec![KeyCode::A],
+            right: vec![KeyCode::D],
+            action: vec![
+                KeyCode::Space,
+                KeyCode::Z, KeyCode::X, KeyCode::C,
+                KeyCode::Tab,
+                KeyCode::ShiftLeft],
+            gamepad: Some(Gamepad::new(1))
+        }
+    }
+
+    // Arrow keys for player two in co-op, or the first gamepad.
+    fn coop_two() -> Self {
+        Self {
+            left: vec![KeyCode::Left],
+            right: vec![KeyCode::Right],
+            action: vec![
+                KeyCode::Return,
+                KeyCode::ShiftRight,
+                KeyCode::ControlRight],
+            gamepad: Some(Gamepad::new(0))
+        }
+    }
+}
+
+#[derive(Component)]
+struct EnemyCapsule;
+
+// Tag for enemies that must be beaten to win the match.
+#[derive(Component)]
+struct BossEnemy;
+
+// Tag for enemies that spin while they move.
+#[derive(Component)]
+struct SpinningEnemy;
+
+// Survival time, score and combo during an endless run.
+#[derive(Component)]
+struct EndlessHud;
+
+// The clock and latest split during time attack.
+#[derive(Component)]
+struct TimeAttackHud;
+
+// The personal best's player, racing alongside.
+#[derive(Component)]
+struct Ghost;
+
+// Per-enemy attack cadence.
+#[derive(Component, Deref, DerefMut)]
+struct EnemyAttack(Timer);
+
+#[derive(Component)]
+struct EnemyProjectile;
+
+#[derive(Component, Reflect, InspectorOptions, Default)]
+#[reflect(Component, InspectorOptions)]
+struct EnemyHealth (f32);
+
+impl ProgressSource for EnemyHealth {
+    fn progress(&self) -> f32 {
+        self.0
+    }
+}
+
+// Full health, scaled for the difficulty when the enemy spawns.
+#[derive(Component)]
+struct EnemyMaxHealth(f32);
 
 #[derive(Component, Reflect, InspectorOptions)]
 #[reflect(InspectorOptions)]
+struct EnemyShield (f32);
+
+#[derive(Component, Reflect, InspectorOptions)]
+#[reflect(InspectorOptions)]
+struct EnemyDirection(f32);
+
+#[derive(Component, Reflect, InspectorOptions, Default)]
+#[reflect(Component, InspectorOptions)]
 struct Speed(f32);
 
 #[derive(Component)]
@@ -505,18 +1248,53 @@ struct Wall;
 #[derive(Component)]
 struct SnowTile;
 
-#[derive(Resource)]
+// Which way a snow tile slides along the ground: 1.0 is right, -1.0 is left.
+#[derive(Component)]
+struct SnowDrift(f32);
+
+// The player that launched a snow tile, so it can't hit its own launcher.
+#[derive(Component)]
+struct LaunchedBy(Entity);
+
+// How often snow falls at the start of a match.
+const SNOW_INTERVAL: Duration = Duration::from_millis(350);
+
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
 struct SnowConfig {
     // How often the snow should spawn.
     timer: Timer,
 }
 
-#[derive(Resource)]
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
 struct ProjectileConfig {
-    // How often the projectiles should spawn.
-    timer: Timer,
+    // Scales how fast every enemy's attack timer runs.
+    rate: f32,
 }
 
+// Set from the console: players don't take damage.
+#[derive(Resource, Default)]
+struct GodMode(bool);
+
+// Randomness that affects the match. Seeded the same on both sides of an
+// online match and saved with every rollback snapshot.
+#[derive(Resource, Clone)]
+struct GameRng(StdRng);
+
+// Steps the physics once per run. Run every frame in PostUpdate, by the
+// rollback session when playing online, or by replay_step.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct PhysicsUpdate;
+
+// The simulation systems, stepped by the rollback session when playing online.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct NetplayUpdate;
+
+// The match systems, stepped at a fixed rate while recording or playing back.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct ReplayUpdate;
+
 
 // Define the collision layers
 #[derive(PhysicsLayer)]
@@ -544,125 +1322,91 @@ struct AnimationIndices {
 #[derive(Component)]
 struct Healthbar;
 
-#[derive(Component)]
-struct Shadowbar;
-
 #[derive(Component)]
 struct PlayerHealthbar;
 
-#[derive(Component)]
-struct PlayerShadowbar;
-
 #[derive(Component, Deref, DerefMut)]
 struct AnimationTimer(Timer);
 
-static RH_OFFSET:f32 = 16.0;
+// The HUD columns that health bars are laid out in.
+#[derive(Resource, Clone, Copy)]
+struct Hud {
+    left: Entity,
+    right: Entity
+}
 
-fn spawn_health_bar(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>
-) {
-    commands.spawn((
-        Name::new("EnemyHealthBarShadow"),
-        Shadowbar,
-        ProgressBarBundle {
-            progresss_bar: ProgressBar {
-                value: 100.0,
-                max_value: 100.0,
-                ..default()
-            },
-            sprite_bundle: SpriteBundle {
-                texture: asset_server.load("healthbar.png"),
-                sprite: Sprite {
-                    anchor: bevy::sprite::Anchor::CenterLeft,
-                    color: Color::rgb(0.1, 0.1, 0.1),
-                    ..default()
-                },
-                transform: Transform::from_xyz(-120.0 + RH_OFFSET, -110.0, 400.0),
-                visibility: Visibility::Hidden,
-                ..default()
-            },
-            ..default()
-        }
-    ));
+// How close and for how long a partner has to hold launch to revive.
+const REVIVE_RANGE: f32 = 40.0;
+const REVIVE_SECS: f32 = 2.0;
+const REVIVE_HEALTH: f32 = 50.0;
 
-    // Spawn the health bar.
-    commands.spawn((
-        Name::new("EnemyHealthBar"),
-        Healthbar,
-        ProgressBarBundle {
-            progresss_bar: ProgressBar {
-                value: 100.0,
-                max_value: 100.0,
-                ..default()
-            },
-            sprite_bundle: SpriteBundle {
-                texture: asset_server.load("healthbar.png"),
-                sprite: Sprite {
-                    anchor: bevy::sprite::Anchor::CenterLeft,
-                    ..default()
+fn spawn_health_bar<S: ProgressSource>(
+    commands: &mut Commands,
+    hud: Entity,
+    label: (&'static str, Color),
+    node: ProgressBarNode,
+    (owner, max_health): (Entity, f32),
+    direction: FillDirection,
+    marker: impl Component
+) {
+    // Each bar gets a row with its label on the outside edge.
+    let (text, color) = label;
+    let row = commands.spawn((
+        Name::new(format!("{text}Row")),
+        NodeBundle {
+            style: Style {
+                flex_direction: match direction {
+                    FillDirection::RightToLeft => FlexDirection::RowReverse,
+                    _ => FlexDirection::Row
                 },
-                transform: Transform::from_xyz(-120.0 + RH_OFFSET, -110.0, 500.0),
-                visibility: Visibility::Hidden,
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(8.0),
                 ..default()
             },
             ..default()
         }
-    ));
-
-    commands.spawn((
-        Name::new("PlayerHealthBarShadow"),
-        PlayerShadowbar,
-        ProgressBarBundle {
-            progresss_bar: ProgressBar {
-                value: 100.0,
-                max_value: 100.0,
-                ..default()
-            },
-            sprite_bundle: SpriteBundle {
-                texture: asset_server.load("player_healthbar-export.png"),
-                sprite: Sprite {
-                    anchor: bevy::sprite::Anchor::CenterLeft,
-                    color: Color::rgb(0.1, 0.1, 0.1),
-                    ..default()
+    ))
+    .with_children(|parent| {
+        spawn_hud_label(parent, text, color);
+        parent.spawn((
+            Name::new("HealthBar"),
+            ProgressBarBinding::<S>::new(owner),
+            marker,
+            ProgressBarNodeBundle {
+                progresss_bar: ProgressBar {
+                    value: max_health,
+                    max_value: max_health,
+                    // Split into 10 HP pips.
+                    step: 10.0,
+                    direction,
                 },
-                transform: Transform::from_xyz(-119.0 + RH_OFFSET, -99.0, 400.0),
-                visibility: Visibility::Hidden,
-                ..default()
-            },
-            ..default()
-        }
-    ));
-
-    // Spawn the health bar.
-    commands.spawn((
-        Name::new("PlayerHealthBar"),
-        PlayerHealthbar,
-        ProgressBarBundle {
-            progresss_bar: ProgressBar {
-                value: 100.0,
-                max_value: 100.0,
-                ..default()
-            },
-            sprite_bundle: SpriteBundle {
-                texture: asset_server.load("player_healthbar-export.png"),
-                sprite: Sprite {
-                    anchor: bevy::sprite::Anchor::CenterLeft,
+                // Dark backdrop, with a red trail showing recent damage and a
+                // green preview while a revive fills the bar back up.
+                layers: ProgressBarLayers {
+                    background: Some(Color::rgb(0.1, 0.1, 0.1)),
+                    trail: Some(Color::rgb(0.8, 0.15, 0.15)),
+                    heal: Some(Color::rgb(0.3, 0.9, 0.4)),
                     ..default()
                 },
-                transform: Transform::from_xyz(-119.0 + RH_OFFSET, -99.0, 500.0),
-                visibility: Visibility::Hidden,
-                ..default()
-            },
-            ..default()
-        }
-    ));
+                // Tinted green, yellow then red, and pulsing when nearly empty.
+                style: ProgressBarStyle::health(),
+                node,
+                node_bundle: default()
+            }
+        ));
+    })
+    .id();
+    commands.entity(hud).add_child(row);
 }
 
 fn setup_game(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    game_mode: Res<GameMode>,
+    versus: Res<VersusMatch>,
+    net_config: Option<Res<NetConfig>>,
+    difficulty: Res<Difficulty>
 ) {
     // Start music.
     commands.spawn((
@@ -708,86 +1452,6 @@ fn setup_game(
     ));
     */
 
-    // Health bar text with drop shadow.
-    commands.spawn((
-        OnInGameScreen,
-        Name::new("PlayerHPTextShadow"),
-        TextBundle::from_section(
-            "Player HP",
-            TextStyle {
-                font_size: 16.0,
-                color: Color::rgb(0.0, 0.0, 0.0),
-                ..default()
-            }
-        )
-        .with_text_alignment(TextAlignment::Center)
-        .with_style(Style {
-            position_type: PositionType::Absolute,
-            bottom: Val::Px(35.0),
-            right: Val::Px(561.0 - RH_OFFSET),
-            ..default()
-        })
-    ));
-    commands.spawn((
-        OnInGameScreen,
-        Name::new("PlayerHPText"),
-        TextBundle::from_section(
-            "Player HP",
-            TextStyle {
-                font_size: 16.0,
-                color: Color::rgb(0.0, 0.28, 1.0),
-                ..default()
-            }
-        )
-        .with_text_alignment(TextAlignment::Center)
-        .with_style(Style {
-            position_type: PositionType::Absolute,
-            bottom: Val::Px(36.0),
-            right: Val::Px(562.0 - RH_OFFSET),
-            ..default()
-        })
-    ));
-
-    // Health bar text with drop shadow.
-    commands.spawn((
-        OnInGameScreen,
-        Name::new("EnemyHpTextShadow"),
-        TextBundle::from_section(
-            "Enemy HP",
-            TextStyle {
-                font_size: 16.0,
-                color: Color::rgb(0.0, 0.0, 0.0),
-                ..default()
-            }
-        )
-        .with_text_alignment(TextAlignment::Center)
-        .with_style(Style {
-            position_type: PositionType::Absolute,
-            bottom: Val::Px(11.0),
-            right: Val::Px(561.0 - RH_OFFSET),
-            ..default()
-        })
-    ));
-    commands.spawn((
-        OnInGameScreen,
-        Name::new("EnemyHpText"),
-        TextBundle::from_section(
-            "Enemy HP",
-            TextStyle {
-                font_size: 16.0,
-                color: Color::rgb(0.0, 0.28, 1.0),
-                ..default()
-            }
-        )
-        .with_text_alignment(TextAlignment::Center)
-        .with_style(Style {
-            position_type: PositionType::Absolute,
-            bottom: Val::Px(12.0),
-            right: Val::Px(562.0 - RH_OFFSET),
-            ..default()
-        })
-    ));
-
     commands.spawn((
         OnInGameScreen,
         Camera2dBundle {
@@ -799,7 +1463,9 @@ fn setup_game(
                 ..default()
             },
             ..default()
-        }
+        },
+        PixelCamera,
+        CameraShake::default()
     ));
 
     // Spawn the bg layers.
@@ -862,82 +1528,182 @@ fn setup_game(
         AnimationTimer(Timer::from_seconds(0.10, TimerMode::Repeating))
     ));
 
-    // Spawn the player with its physics, sprite, and tween animations.
-    // The sprite is a child of the capsule/SpatialBundle so it can
-    // rotate independently.
+    // Health bars are laid out along the bottom of the screen, with the
+    // players on the left and the rival on the right in versus.
+    let hud_column = |align_items| NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Column,
+            align_items,
+            row_gap: Val::Px(4.0),
+            ..default()
+        },
+        ..default()
+    };
+    let mut hud = Hud { left: Entity::PLACEHOLDER, right: Entity::PLACEHOLDER };
     commands.spawn((
         OnInGameScreen,
-        Name::new("PlayerEntity"),
-        PlayerHealth(100.0),
-        PlayerCapsule,
-        SpatialBundle {
-            visibility: Visibility::Inherited,
-            transform: Transform::from_xyz(0.0, 0.0, 100.0),
+        Name::new("Hud"),
+        // Sized to cover the game area, wherever it is in the window.
+        LetterboxedUi,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                padding: UiRect {
+                    left: Val::Px(16.0),
+                    right: Val::Px(16.0),
+                    bottom: Val::Px(8.0),
+                    ..default()
+                },
+                justify_content: JustifyContent::SpaceBetween,
+                align_items: AlignItems::FlexEnd,
+                ..default()
+            },
             ..default()
-        },
-        RigidBody::Dynamic,
-        Collider::capsule(32.0, 16.0),
-        CollisionLayers::new([Layer::Player],
-            [Layer::Ground, Layer::Wall, Layer::Snow, Layer::EnemyProjectile]),
-        LockedAxes::new().lock_rotation(),
-        Friction::ZERO.with_combine_rule(CoefficientCombine::Min),
-        Restitution::ZERO.with_combine_rule(CoefficientCombine::Min),
-        Mass(1.0),
-        Speed(32.0)
+        }
     ))
     .with_children(|parent| {
-        let base_duration_ms: u64 = 500;
-        let tween = Tween::new(
-            EaseFunction::ElasticInOut,
-            std::time::Duration::from_millis(base_duration_ms),
-            TransformRotationLens {
-                start: Quat::from_axis_angle(Vec3::Z, -std::f32::consts::PI / 9.),
-                end: Quat::from_axis_angle(Vec3::Z, std::f32::consts::PI / 9.),
+        hud.left = parent.spawn(hud_column(AlignItems::FlexStart)).id();
+        hud.right = parent.spawn(hud_column(AlignItems::FlexEnd)).id();
+    });
+    commands.insert_resource(hud);
+
+    // Spawn the players and their health bars.
+    match *game_mode {
+        GameMode::Single | GameMode::Endless | GameMode::TimeAttack => {
+            spawn_player(&mut commands, &asset_server, PlayerSetup {
+                name: "PlayerEntity",
+                slot: 0,
+                x: 0.0,
+                facing: 1.0,
+                bindings: PlayerBindings::single(),
+                tint: Color::WHITE,
+                hud: hud.left,
+                label: ("Player HP", Color::rgb(0.0, 0.28, 1.0)),
+                bar_direction: FillDirection::LeftToRight
+            });
+        },
+        GameMode::Coop => {
+            spawn_player(&mut commands, &asset_server, PlayerSetup {
+                name: "PlayerEntity",
+                slot: 0,
+                x: -32.0,
+                facing: 1.0,
+                bindings: PlayerBindings::coop_one(),
+                tint: Color::WHITE,
+                hud: hud.left,
+                label: ("Player HP", Color::rgb(0.0, 0.28, 1.0)),
+                bar_direction: FillDirection::LeftToRight
+            });
+            spawn_player(&mut commands, &asset_server, PlayerSetup {
+                name: "PlayerTwoEntity",
+                slot: 1,
+                x: 32.0,
+                facing: 1.0,
+                bindings: PlayerBindings::coop_two(),
+                tint: Color::rgb(1.0, 0.6, 0.6),
+                hud: hud.left,
+                label: ("P2 HP", Color::rgb(1.0, 0.3, 0.3)),
+                bar_direction: FillDirection::LeftToRight
+            });
+        },
+        GameMode::Versus => {
+            // Players face each other from opposite sides of the arena.
+            let player_one = spawn_player(&mut commands, &asset_server, PlayerSetup {
+                name: "PlayerEntity",
+                slot: 0,
+                x: -96.0,
+                facing: 1.0,
+                bindings: PlayerBindings::coop_one(),
+                tint: Color::WHITE,
+                hud: hud.left,
+                label: ("P1 HP", Color::rgb(0.0, 0.28, 1.0)),
+                bar_direction: FillDirection::LeftToRight
+            });
+            let player_two = spawn_player(&mut commands, &asset_server, PlayerSetup {
+                name: "PlayerTwoEntity",
+                slot: 1,
+                x: 96.0,
+                facing: -1.0,
+                bindings: PlayerBindings::coop_two(),
+                tint: Color::rgb(1.0, 0.6, 0.6),
+                // Mirrored on the right so each bar empties from the middle out
+                // towards its own edge of the screen.
+                hud: hud.right,
+                label: ("P2 HP", Color::rgb(1.0, 0.3, 0.3)),
+                bar_direction: FillDirection::RightToLeft
+            });
+
+            // Online the local player gets the whole keyboard and the other
+            // player is driven by inputs from the network.
+            if let (true, Some(config)) = (versus.online, net_config) {
+                let (local, remote) = match config.slot {
+                    0 => (player_one, player_two),
+                    _ => (player_two, player_one)
+                };
+                commands.entity(local).insert(PlayerBindings::single());
+                commands.entity(remote).remove::<PlayerBindings>();
             }
-        )
-        .with_repeat_count(RepeatCount::Infinite)
-        .with_repeat_strategy(RepeatStrategy::Repeat);
 
-        parent.spawn((
-            PlayerSprite,
-            SpriteBundle {
-                texture: asset_server.load("player_pixel_1.png"),
-                ..default()
-            },
-            Animator::new(tween)));
-    });
+            // Split the arena down the middle. Only players collide with it.
+            commands.spawn((
+                OnInGameScreen,
+                Name::new("WallEntityMid"),
+                Wall,
+                SpatialBundle {
+                    visibility: Visibility::Hidden,
+                    transform: Transform::from_xyz(0.0, 0.0, 0.0),
+                    ..default()
+                },
+                RigidBody::Static,
+                Collider::cuboid(8.0, 240.0),
+                CollisionLayers::new([Layer::Wall],
+                    [Layer::Player]),
+            ));
+        }
+    }
 
-    // Spawn the enemy with its physics, sprite, and tween animations.
-    // The sprite is a child of the capsule/SpatialBundle so it can
-    // rotate independently.
+    // Spawn every enemy on the roster that is due at the start of the match.
+    // The rest are spawned over time by spawn_roster_enemies.
+    let mut roster = match *game_mode {
+        GameMode::Versus => EnemyRoster::empty(),
+        GameMode::TimeAttack => EnemyRoster::boss_only(),
+        _ => EnemyRoster::default()
+    };
+    for (kind, x) in roster.tick(0.0) {
+        spawn_enemy(&mut commands, &asset_server, hud, *difficulty, kind, x);
+    }
+    commands.insert_resource(roster);
+
+    commands.insert_resource(MatchStats::default());
+
+    // Scorekeeping for the solo modes, left over from the last match.
+    commands.remove_resource::<EndlessRun>();
+    commands.remove_resource::<TimeAttackRun>();
+
+    // Endless runs keep score in the corner.
+    if *game_mode == GameMode::Endless {
+        commands.insert_resource(EndlessRun::default());
+        let text = commands.spawn((
+            Name::new("EndlessHud"),
+            EndlessHud,
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 16.0,
+                    color: TEXT_COLOR,
+                    ..default()
+                }
+            ).with_text_alignment(TextAlignment::Right)
+        )).id();
+        commands.entity(hud.right).add_child(text);
+    }
+
+    // Spawn the ground.
     commands.spawn((
         OnInGameScreen,
-        Name::new("EnemyEntity"),
-        EnemyCapsule,
-        EnemyDirection(1.0),
-        EnemyHealth(100.0),
-        SpriteBundle {
-            texture: asset_server.load("enemy.png"),
-            transform: Transform::from_xyz(64.0, 96.0, 100.0)
-                .with_scale(Vec3::new(2.0, 2.0, 1.0)),
-            ..default()
-        },
-        RigidBody::Dynamic,
-        Collider::ball(8.0),
-        CollisionLayers::new([Layer::Enemy],
-            [Layer::Snow]),
-        Sensor,
-        Friction::ZERO.with_combine_rule(CoefficientCombine::Min),
-        Restitution::ZERO.with_combine_rule(CoefficientCombine::Min),
-        GravityScale(0.0),
-        Mass(0.0),
-        Speed(32.0)
-    ));
-
-    // Spawn the ground.
-    commands.spawn((
-        OnInGameScreen,
-        Name::new("Ground"),
+        Name::new("Ground"),
         SpriteBundle {
             sprite: Sprite {
                 color: Color::rgb(0.95, 0.95, 1.0),
@@ -984,6 +1750,126 @@ fn setup_game(
     ));
 }
 
+fn spawn_hud_label(
+    parent: &mut ChildBuilder,
+    text: &'static str,
+    color: Color
+) {
+    // Health bar text with drop shadow. The shadow sets the size and the
+    // text sits just above and left of it.
+    parent.spawn((
+        Name::new(format!("{text}TextShadow")),
+        TextBundle::from_section(
+            text,
+            TextStyle {
+                font_size: 16.0,
+                color: Color::rgb(0.0, 0.0, 0.0),
+                ..default()
+            }
+        )
+    ))
+    .with_children(|parent| {
+        parent.spawn((
+            Name::new(format!("{text}Text")),
+            TextBundle::from_section(
+                text,
+                TextStyle {
+                    font_size: 16.0,
+                    color,
+                    ..default()
+                }
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(-1.0),
+                top: Val::Px(-1.0),
+                ..default()
+            })
+        ));
+    });
+}
+
+fn spawn_player(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    setup: PlayerSetup
+) -> Entity {
+    let PlayerSetup { name, slot, x, facing, bindings, tint, hud, label, bar_direction } = setup;
+
+    // Spawn the player with its physics, sprite, and tween animations.
+    // The sprite is a child of the capsule/SpatialBundle so it can
+    // rotate independently.
+    let player = commands.spawn((
+        player_capsule(name, slot, x, facing),
+        bindings,
+        PlayerTint(tint)
+    ))
+    .with_children(|parent| {
+        let base_duration_ms: u64 = 500;
+        let tween = Tween::new(
+            EaseFunction::ElasticInOut,
+            std::time::Duration::from_millis(base_duration_ms),
+            TransformRotationLens {
+                start: Quat::from_axis_angle(Vec3::Z, -std::f32::consts::PI / 9.),
+                end: Quat::from_axis_angle(Vec3::Z, std::f32::consts::PI / 9.),
+            }
+        )
+        .with_repeat_count(RepeatCount::Infinite)
+        .with_repeat_strategy(RepeatStrategy::Repeat);
+
+        parent.spawn((
+            PlayerSprite,
+            SpriteBundle {
+                texture: asset_server.load("player_pixel_1.png"),
+                sprite: Sprite {
+                    color: tint,
+                    ..default()
+                },
+                ..default()
+            },
+            Animator::new(tween)));
+    })
+    .id();
+    spawn_health_bar::<PlayerHealth>(
+        commands,
+        hud,
+        label,
+        ProgressBarNode {
+            texture: asset_server.load("player_healthbar-export.png"),
+            size: Vec2::new(256.0, 14.0)
+        },
+        (player, 100.0),
+        bar_direction,
+        PlayerHealthbar
+    );
+    player
+}
+
+// The player's capsule with its physics, without the sprite or health bar.
+fn player_capsule(name: &'static str, slot: usize, x: f32, facing: f32) -> impl Bundle {
+    (
+        OnInGameScreen,
+        Name::new(name),
+        PlayerHealth(100.0),
+        PlayerCapsule,
+        (PlayerInput::default(), PlayerSlot(slot), PlayerFacing(facing)),
+        SpatialBundle {
+            visibility: Visibility::Inherited,
+            transform: Transform::from_xyz(x, 0.0, 100.0),
+            ..default()
+        },
+        RigidBody::Dynamic,
+        Collider::capsule(32.0, 16.0),
+        CollisionLayers::new([Layer::Player],
+            [Layer::Ground, Layer::Wall, Layer::Snow, Layer::EnemyProjectile]),
+        LockedAxes::new().lock_rotation(),
+        Friction::ZERO.with_combine_rule(CoefficientCombine::Min),
+        Restitution::ZERO.with_combine_rule(CoefficientCombine::Min),
+        Mass(1.0),
+        Speed(32.0)
+    )
+}
+
 fn anim_snow_fx (
     time: Res<Time>,
     mut query: Query<(&AnimationIndices, &mut AnimationTimer, &mut TextureAtlasSprite)>
@@ -1000,8 +1886,119 @@ fn anim_snow_fx (
     }
 }
 
+fn spawn_enemy(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    hud: Hud,
+    difficulty: Difficulty,
+    kind: EnemyKind,
+    x: f32
+) {
+    let archetype = kind.archetype();
+    // Tougher enemies on harder difficulties.
+    let max_health = archetype.health * difficulty.enemy_health_factor();
+
+    // Fly-across enemies head towards the far side of the screen.
+    let direction = match archetype.movement {
+        EnemyMovement::FlyAcross if x > 0.0 => -1.0,
+        _ => 1.0
+    };
+
+    // Spawn the enemy with its physics and sprite.
+    let mut enemy = commands.spawn((
+        OnInGameScreen,
+        Name::new(archetype.name),
+        EnemyCapsule,
+        kind,
+        archetype.movement,
+        EnemyDirection(direction),
+        EnemyHealth(max_health),
+        EnemyMaxHealth(max_health),
+        SpriteBundle {
+            texture: asset_server.load(archetype.sprite),
+            sprite: Sprite {
+                color: archetype.tint,
+                ..default()
+            },
+            transform: Transform::from_xyz(x, archetype.spawn_y, 100.0)
+                .with_scale(Vec3::new(archetype.scale, archetype.scale, 1.0)),
+            ..default()
+        },
+        Collider::ball(archetype.radius),
+        CollisionLayers::new([Layer::Enemy],
+            [Layer::Snow]),
+        Friction::ZERO.with_combine_rule(CoefficientCombine::Min),
+        Restitution::ZERO.with_combine_rule(CoefficientCombine::Min),
+        GravityScale(0.0),
+        Mass(0.0),
+        Speed(archetype.speed * difficulty.enemy_speed_factor())
+    ));
+
+    // Solid enemies are kinematic so the snow can't push them around.
+    if archetype.blocks_snow {
+        enemy.insert(RigidBody::Kinematic);
+    } else {
+        enemy.insert((RigidBody::Dynamic, Sensor));
+    }
+    if let Some(interval) = archetype.attack_interval {
+        enemy.insert(EnemyAttack(Timer::from_seconds(interval, TimerMode::Repeating)));
+    }
+    if archetype.shield > 0.0 {
+        enemy.insert(EnemyShield(archetype.shield));
+    }
+    if archetype.spins {
+        enemy.insert(SpinningEnemy);
+    }
+    let owner = enemy.id();
+    if archetype.boss {
+        enemy.insert(BossEnemy);
+        spawn_health_bar::<EnemyHealth>(
+            commands,
+            hud.left,
+            ("Enemy HP", Color::rgb(0.0, 0.28, 1.0)),
+            ProgressBarNode {
+                texture: asset_server.load("healthbar.png"),
+                size: Vec2::new(384.0, 24.0)
+            },
+            (owner, max_health),
+            FillDirection::LeftToRight,
+            Healthbar
+        );
+    }
+
+    // Every enemy also gets a small bar floating over it, hidden while it's
+    // unhurt. The texture is squashed down to 24x3.
+    commands.spawn((
+        OnInGameScreen,
+        Name::new("EnemyFloatingBar"),
+        ProgressBarBinding::<EnemyHealth>::new(owner),
+        ProgressBarFollow::new(owner, Vec3::new(-12.0, archetype.radius * archetype.scale + 6.0, 400.0))
+            .with_hide_when_full(1.5),
+        ProgressBarBundle::new(max_health, asset_server.load("player_healthbar-export.png"))
+            .with_transform(Transform::from_scale(Vec3::new(0.1875, 0.5, 1.0)))
+            .with_layers(ProgressBarLayers {
+                background: Some(Color::rgb(0.1, 0.1, 0.1)),
+                ..default()
+            })
+    ));
+}
+
+fn spawn_roster_enemies(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+    hud: Res<Hud>,
+    (difficulty, adaptive): (Res<Difficulty>, Res<AdaptiveDifficulty>),
+    mut roster: ResMut<EnemyRoster>
+) {
+    // Adaptive difficulty brings the next enemies in sooner or later.
+    for (kind, x) in roster.tick(time.delta_seconds() * adaptive.factor) {
+        spawn_enemy(&mut commands, &asset_server, *hud, *difficulty, kind, x);
+    }
+}
+
 fn anim_enemy(
-    mut query: Query<(&mut AngularVelocity, &EnemyDirection), With<EnemyCapsule>>
+    mut query: Query<(&mut AngularVelocity, &EnemyDirection), With<SpinningEnemy>>
 ) {
     for (mut angular_velocity, dir) in query.iter_mut() {
         angular_velocity.0 += 1.0 * dir.0;
@@ -1013,20 +2010,22 @@ fn anim_enemy(
 }
 
 fn move_enemy(
-    mut enemy: Query<(&mut LinearVelocity, &mut EnemyDirection, &Transform), With<EnemyCapsule>>
+    mut enemy: Query<(&mut LinearVelocity, &mut EnemyDirection, &EnemyMovement, &Speed, &Transform), With<EnemyCapsule>>
 ) {
-    for (mut linear_vel, mut dir, xform) in enemy.iter_mut() {
-        // Flip the movement direction when x bounds are hit.
-        if xform.translation.x < -130.0 && dir.0 < 0.0
-        {
-            dir.0 *= -1.0;
-        }
-        if xform.translation.x > 130.0 && dir.0 > 0.0
-        {
-            dir.0 *= -1.0;
+    for (mut linear_vel, mut dir, movement, speed, xform) in enemy.iter_mut() {
+        if let EnemyMovement::Patrol { min_x, max_x } = *movement {
+            // Flip the movement direction when x bounds are hit.
+            if xform.translation.x < min_x && dir.0 < 0.0
+            {
+                dir.0 *= -1.0;
+            }
+            if xform.translation.x > max_x && dir.0 > 0.0
+            {
+                dir.0 *= -1.0;
+            }
         }
 
-        let enemy_speed = 40.0;
+        let enemy_speed = speed.0;
         let enemy_friction = 0.8;
 
         // Apply velocity.
@@ -1036,66 +2035,97 @@ fn move_enemy(
     }
 }
 
-fn anim_player(
+fn read_player_input(
     keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    mut players: Query<(&PlayerBindings, &mut PlayerInput, Has<Downed>)>
+) {
+    for (bindings, mut input, downed) in &mut players {
+        // Downed players can't do anything until they're revived.
+        if downed {
+            *input = PlayerInput::default();
+            continue;
+        }
+
+        let mut left = keys.any_pressed(bindings.left.iter().copied());
+        let mut right = keys.any_pressed(bindings.right.iter().copied());
+        let mut action = keys.any_pressed(bindings.action.iter().copied());
+        if let Some(gamepad) = bindings.gamepad {
+            let stick_x = axes
+                .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
+                .unwrap_or(0.0);
+            left |= stick_x < -0.5 ||
+                buttons.pressed(GamepadButton::new(gamepad, GamepadButtonType::DPadLeft));
+            right |= stick_x > 0.5 ||
+                buttons.pressed(GamepadButton::new(gamepad, GamepadButtonType::DPadRight));
+            action |= buttons.any_pressed([
+                GamepadButton::new(gamepad, GamepadButtonType::South),
+                GamepadButton::new(gamepad, GamepadButtonType::East),
+                GamepadButton::new(gamepad, GamepadButtonType::RightTrigger)]);
+        }
+
+        let new_input = PlayerInput { left, right, action };
+        if *input != new_input {
+            *input = new_input;
+        }
+    }
+}
+
+fn anim_player(
+    players: Query<(&PlayerInput, &Children), With<PlayerCapsule>>,
     mut animators: Query<&mut Animator<Transform>, With<PlayerSprite>>
 ) {
-    let hold_action = keys.any_pressed([
-        KeyCode::Space,
-        KeyCode::Z, KeyCode::X, KeyCode::C,
-        KeyCode::Return,
-        KeyCode::Tab,
-        KeyCode::ShiftLeft,
-        KeyCode::ShiftRight]);
-    for mut animator in animators.iter_mut() {
-        let base_duration_ms: u64 = 500;
-        let norm_speed = animator.speed() < 1.5f32;
-        let fast_speed = animator.speed() > 1.5f32;
-
-        if norm_speed && hold_action {
-            let tween = Tween::new(
-                EaseFunction::ElasticInOut,
-                std::time::Duration::from_millis(base_duration_ms),
-                TransformRotationLens {
-                    // These angles lean the sprite forward. (Towards screen right.)
-                    start: Quat::from_axis_angle(Vec3::Z, -std::f32::consts::PI / 4.),
-                    end: Quat::from_axis_angle(Vec3::Z, std::f32::consts::PI / 12.),
-                }
-            )
-            .with_repeat_count(RepeatCount::Infinite)
-            .with_repeat_strategy(RepeatStrategy::Repeat);
-            animator.set_tweenable(tween);
-            animator.set_speed(2.0);
-        } else if fast_speed && !hold_action{
-            let tween = Tween::new(
-                EaseFunction::ElasticInOut,
-                std::time::Duration::from_millis(base_duration_ms),
-                TransformRotationLens {
-                    start: Quat::from_axis_angle(Vec3::Z, -std::f32::consts::PI / 9.),
-                    end: Quat::from_axis_angle(Vec3::Z, std::f32::consts::PI / 9.),
-                }
-            )
-            .with_repeat_count(RepeatCount::Infinite)
-            .with_repeat_strategy(RepeatStrategy::Repeat);
-            animator.set_tweenable(tween);
-            animator.set_speed(1.0);
+    for (input, children) in &players {
+        let hold_action = input.action;
+        let mut player_animators = animators.iter_many_mut(children);
+        while let Some(mut animator) = player_animators.fetch_next() {
+            let base_duration_ms: u64 = 500;
+            let norm_speed = animator.speed() < 1.5f32;
+            let fast_speed = animator.speed() > 1.5f32;
+
+            if norm_speed && hold_action {
+                let tween = Tween::new(
+                    EaseFunction::ElasticInOut,
+                    std::time::Duration::from_millis(base_duration_ms),
+                    TransformRotationLens {
+                        // These angles lean the sprite forward. (Towards screen right.)
+                        start: Quat::from_axis_angle(Vec3::Z, -std::f32::consts::PI / 4.),
+                        end: Quat::from_axis_angle(Vec3::Z, std::f32::consts::PI / 12.),
+                    }
+                )
+                .with_repeat_count(RepeatCount::Infinite)
+                .with_repeat_strategy(RepeatStrategy::Repeat);
+                animator.set_tweenable(tween);
+                animator.set_speed(2.0);
+            } else if fast_speed && !hold_action{
+                let tween = Tween::new(
+                    EaseFunction::ElasticInOut,
+                    std::time::Duration::from_millis(base_duration_ms),
+                    TransformRotationLens {
+                        start: Quat::from_axis_angle(Vec3::Z, -std::f32::consts::PI / 9.),
+                        end: Quat::from_axis_angle(Vec3::Z, std::f32::consts::PI / 9.),
+                    }
+                )
+                .with_repeat_count(RepeatCount::Infinite)
+                .with_repeat_strategy(RepeatStrategy::Repeat);
+                animator.set_tweenable(tween);
+                animator.set_speed(1.0);
+            }
         }
     }
 }
 
 fn move_player(
-    keys: Res<Input<KeyCode>>,
-    mut players: Query<(&mut LinearVelocity, &Speed), With<PlayerCapsule>>
+    mut players: Query<(&mut LinearVelocity, &Speed, &PlayerInput), With<PlayerCapsule>>
 ) {
-    for (mut linear_vel, player_speed) in &mut players {
+    for (mut linear_vel, player_speed, input) in &mut players {
         // Only move left and right.
         let mut direction = Vec2::ZERO;
-        let left = keys.any_pressed([KeyCode::A, KeyCode::Left]);
-        let right = keys.any_pressed([KeyCode::D, KeyCode::Right]);
-        if left {
+        if input.left {
             direction += Vec2::new(-1.0, 0.0);
         }
-        if right {
+        if input.right {
             direction += Vec2::new(1.0, 0.0);
         }
         direction.y = 0.0;
@@ -1109,27 +2139,35 @@ fn move_player(
 }
 
 fn setup_snow_and_projectiles(
-    mut commands: Commands
+    mut commands: Commands,
+    cli: Res<Cli>,
+    difficulty: Res<Difficulty>,
+    mut adaptive: ResMut<AdaptiveDifficulty>
 ) {
     commands.insert_resource(
         SnowConfig {
             // Create the repeating timer.
-            timer: Timer::new(std::time::Duration::from_millis(350), TimerMode::Repeating)
+            timer: Timer::new(SNOW_INTERVAL, TimerMode::Repeating)
         }
     );
     commands.insert_resource(
         ProjectileConfig {
-            // Create the repeating timer.
-            timer: Timer::new(std::time::Duration::from_millis(250), TimerMode::Repeating)
+            // Each enemy has its own attack timer, this just scales them all.
+            rate: difficulty.projectile_rate()
         }
     );
+    adaptive.restart();
+    let rng = cli.seed.map(StdRng::seed_from_u64).unwrap_or_else(StdRng::from_entropy);
+    commands.insert_resource(GameRng(rng));
 }
 
 fn spawn_snow(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     time: Res<Time>,
+    game_mode: Res<GameMode>,
     mut config: ResMut<SnowConfig>,
+    mut rng: ResMut<GameRng>,
     snow: Query<Entity, With<SnowTile>>
 ) {
     // Tick the snow timer.
@@ -1139,80 +2177,107 @@ fn spawn_snow(
     let snow_count = snow.iter().count();
     if config.timer.finished() && snow_count < MAX_SNOW {
         // Pick a random snow sprite each time.
-        let mut rng = rand::thread_rng();
+        let rng = &mut rng.0;
         let sprites = vec!["snow_1.png", "snow_2.png"];
         let sprite_idx: usize = rng.gen_range(0..sprites.len());
         let path = sprites[sprite_idx];
-        // Spawn the snow sprite with its physics components.
-        commands.spawn((
-            OnInGameScreen,
-            Name::new("SnowTile"),
-            SnowTile,
-            SpriteBundle {
-                texture: asset_server.load(path),
-                // Just above the player in z-order.
-                transform: Transform::from_xyz(192.0, 0.0, 300.0),
-                ..default()
+        // In versus the snow drops in the middle and slides to either side.
+        let (position, drift) = match *game_mode {
+            GameMode::Versus => {
+                let drift = if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+                (Vec2::new(0.0, 100.0), drift)
             },
-            RigidBody::Dynamic,
-            Collider::cuboid(32.0, 32.0),
-            CollisionLayers::new([Layer::Snow],
-                [Layer::Player, Layer::Ground, Layer::Enemy, Layer::EnemyProjectile]),
-            Friction::ZERO.with_combine_rule(CoefficientCombine::Min),
-            Restitution::ZERO.with_combine_rule(CoefficientCombine::Min),
-            Mass(100.0),
-            Speed(24.0)
+            _ => (Vec2::new(192.0, 0.0), -1.0)
+        };
+        // Just above the player in z-order.
+        commands.spawn(snow_tile(
+            asset_server.load(path),
+            Transform::from_xyz(position.x, position.y, 300.0),
+            drift
         ));
     }
 }
 
+// The snow sprite with its physics components.
+fn snow_tile(texture: Handle<Image>, transform: Transform, drift: f32) -> impl Bundle {
+    (
+        OnInGameScreen,
+        Name::new("SnowTile"),
+        SnowTile,
+        SnowDrift(drift),
+        SpriteBundle {
+            texture,
+            transform,
+            ..default()
+        },
+        RigidBody::Dynamic,
+        Collider::cuboid(32.0, 32.0),
+        CollisionLayers::new([Layer::Snow],
+            [Layer::Player, Layer::Ground, Layer::Enemy, Layer::EnemyProjectile]),
+        Friction::ZERO.with_combine_rule(CoefficientCombine::Min),
+        Restitution::ZERO.with_combine_rule(CoefficientCombine::Min),
+        Mass(100.0),
+        Speed(24.0)
+    )
+}
+
 fn move_snow(
-    mut snow: Query<(&mut LinearVelocity, &Speed), With<SnowTile>>
+    mut snow: Query<(&mut LinearVelocity, &Speed, &SnowDrift), With<SnowTile>>
 ) {
-    for (mut linear_vel, speed) in &mut snow {
-        linear_vel.x += Vec2::new(-1.0, 0.0).normalize_or_zero().x * speed.0;
+    for (mut linear_vel, speed, drift) in &mut snow {
+        linear_vel.x += Vec2::new(drift.0, 0.0).normalize_or_zero().x * speed.0;
     }
 }
 
 fn spawn_enemy_projectiles(
     mut commands: Commands,
-    enemy_query: Query<&Transform, With<EnemyCapsule>>,
+    mut enemy_query: Query<(&Transform, &mut EnemyAttack), With<EnemyCapsule>>,
     asset_server: Res<AssetServer>,
     time: Res<Time>,
-    mut config: ResMut<ProjectileConfig>
+    config: Res<ProjectileConfig>,
+    adaptive: Res<AdaptiveDifficulty>
 ) {
-    // Tick the snow timer.
-    config.timer.tick(time.delta());
+    for (xform, mut attack) in &mut enemy_query {
+        // Tick each enemy's attack timer.
+        attack.tick(time.delta().mul_f32(config.rate * adaptive.factor));
 
-    if config.timer.finished() {
-        // Spawn the projectile sprite with its physics components.
-        commands.spawn((
-            OnInGameScreen,
-            Name::new("EnemyProjectile"),
-            EnemyProjectile,
-            SpriteBundle {
-                texture: asset_server.load("enemy_projectile.png"),
-                transform: enemy_query.single().clone(),
-                ..default()
-            },
-            RigidBody::Dynamic,
-            Collider::cuboid(8.0, 8.0),
-            CollisionLayers::new([Layer::EnemyProjectile],
-                [Layer::Player, Layer::Snow]),
-            Friction::ZERO.with_combine_rule(CoefficientCombine::Min),
-            Restitution::ZERO.with_combine_rule(CoefficientCombine::Min),
-            Mass(10.0),
-            LinearVelocity(Vec2::new(0.0, 200.0)),
-            Speed(10.0)
-        ));
+        if !attack.finished() {
+            continue;
+        }
+
+        commands.spawn(enemy_projectile(asset_server.load("enemy_projectile.png"), *xform));
     }
 }
 
+// The projectile sprite with its physics components.
+fn enemy_projectile(texture: Handle<Image>, transform: Transform) -> impl Bundle {
+    (
+        OnInGameScreen,
+        Name::new("EnemyProjectile"),
+        EnemyProjectile,
+        SpriteBundle {
+            texture,
+            transform,
+            ..default()
+        },
+        RigidBody::Dynamic,
+        Collider::cuboid(8.0, 8.0),
+        CollisionLayers::new([Layer::EnemyProjectile],
+            [Layer::Player, Layer::Snow]),
+        Friction::ZERO.with_combine_rule(CoefficientCombine::Min),
+        Restitution::ZERO.with_combine_rule(CoefficientCombine::Min),
+        Mass(10.0),
+        LinearVelocity(Vec2::new(0.0, 200.0)),
+        Speed(10.0)
+    )
+}
+
 fn move_enemy_projectiles(
+    mut rng: ResMut<GameRng>,
     mut projectiles: Query<(&mut AngularVelocity, &mut LinearVelocity, &Speed), With<EnemyProjectile>>
 ) {
     for (mut ang_vel, mut lin_vel, speed) in &mut projectiles {
-        let mut rng = rand::thread_rng();
+        let rng = &mut rng.0;
         let av: f32 = rng.gen_range(-1.0..1.0);
         let x_vel: f32 = rng.gen_range(-3.0..3.0);
         let friction: f32 = 0.8;
@@ -1227,179 +2292,1455 @@ fn move_enemy_projectiles(
 
 fn collide_snow_with_player(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    keys: Res<Input<KeyCode>>,
-    player: Query<Entity, With<PlayerCapsule>>,
-    mut collisions: Query<(Entity, &mut LinearVelocity, &CollidingEntities), With<SnowTile>>
+    game_mode: Res<GameMode>,
+    players: Query<(&PlayerInput, &PlayerFacing), With<PlayerCapsule>>,
+    mut collisions: Query<(Entity, &mut LinearVelocity, &mut SnowDrift, &CollidingEntities, &Transform), With<SnowTile>>,
+    mut bursts: EventWriter<ParticleBurst>,
+    (mut stats, mut adaptive): (ResMut<MatchStats>, ResMut<AdaptiveDifficulty>)
 ) {
     let force = 160.0;
-    let hold_action = keys.any_pressed([
-        KeyCode::Space,
-        KeyCode::Z, KeyCode::X, KeyCode::C,
-        KeyCode::Return,
-        KeyCode::Tab,
-        KeyCode::ShiftLeft,
-        KeyCode::ShiftRight]);
-    for (entity, mut linear_vel, colliding_entities) in &mut collisions {
-        if hold_action && colliding_entities.contains(&player.single())
+    for (entity, mut linear_vel, mut drift, colliding_entities, xform) in &mut collisions {
+        // Launch the tile if any player touching it is holding the action key.
+        let launcher = colliding_entities
+            .iter()
+            .copied()
+            .find(|e| players.get(*e).is_ok_and(|(input, _)| input.action));
+        if let Some(launcher) = launcher
         {
-            linear_vel.x += Vec2::new(1.0, 0.0).x * force;
-            linear_vel.y += Vec2::new(0.0, 2.0).y * force;
-
-            // Add the toDelete component.
-            commands.entity(entity).insert(ToDelete);
+            let Ok((_, facing)) = players.get(launcher) else {
+                continue;
+            };
+            if *game_mode == GameMode::Versus {
+                // Mirrored for whichever side launched it, and flatter so
+                // it travels across the arena instead of up at the sky.
+                linear_vel.x += Vec2::new(facing.0, 0.0).x * force;
+                linear_vel.y += Vec2::new(0.0, 0.5).y * force;
+                drift.0 = facing.0;
+            } else {
+                linear_vel.x += Vec2::new(facing.0, 0.0).x * force;
+                linear_vel.y += Vec2::new(0.0, 2.0).y * force;
+            }
 
-            // Play ice hit sound with random speed.
-            let mut rng = rand::thread_rng();
-            let audio_speed: f32 = rng.gen_range(0.8..1.2);
-            commands.spawn((
-                OnInGameScreen,
-                AudioBundle {
-                    source: asset_server.load("ice-hit-mix.ogg"),
-                    settings: PlaybackSettings {
-                        mode: bevy::audio::PlaybackMode::Despawn,
-                        volume: bevy::audio::Volume::Relative(bevy::audio::VolumeLevel::new(0.03)),
-                        speed: audio_speed,
-                        ..default()
-                    },
-                    ..default()
-                },
-                IceWasHitSound,
+            // Add the toDelete component, and a sparkle trail while it flies.
+            commands.entity(entity).insert((
+                ToDelete,
+                LaunchedBy(launcher),
+                ParticleEmitter::new(sparkle_trail(), 30.0).with_area(Vec2::splat(16.0))
             ));
+            bursts.send(ParticleBurst {
+                effect: snow_puff(),
+                position: xform.translation.truncate().extend(150.0),
+                count: 12
+            });
+            stats.launch();
+            adaptive.record_throw();
         }
     }
 }
 
-fn collide_snow_with_enemy(
+fn collide_snow_with_rival(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    enemy: Query<Entity, With<EnemyCapsule>>,
-    mut enemy_health: Query<&mut EnemyHealth>,
-    mut collisions: Query<(Entity, &CollidingEntities), (With<SnowTile>, Without<DidDamage>)>
+    mut rng: ResMut<GameRng>,
+    god_mode: Res<GodMode>,
+    mut players: Query<&mut PlayerHealth, (With<PlayerCapsule>, Without<Downed>)>,
+    collisions: Query<(Entity, &LaunchedBy, &CollidingEntities, &Transform), (With<SnowTile>, Without<DidDamage>)>,
+    mut damage_events: EventWriter<DamageEvent>,
+    (mut stats, mut adaptive): (ResMut<MatchStats>, ResMut<AdaptiveDifficulty>)
 ) {
-    for (entity, colliding_entities) in &mut collisions {
-        if colliding_entities.contains(&enemy.single())
+    for (entity, launched_by, colliding_entities, xform) in &collisions {
+        // Launched snow hurts any player other than the one who launched it.
+        let hit = colliding_entities
+            .iter()
+            .copied()
+            .find(|e| *e != launched_by.0 && players.contains(*e));
+        if let Some((target, Ok(mut player_health))) = hit.map(|e| (e, players.get_mut(e)))
         {
-            let mut rng = rand::thread_rng();
-            let damage: f32 = rng.gen_range(1.0..5.0);
-            // Debugging... let damage: f32 = rng.gen_range(10.0..20.0);
-            enemy_health.single_mut().0 -= damage;
+            let damage: f32 = rng.0.gen_range(5.0..12.0);
+            if !god_mode.0 {
+                player_health.0 -= damage;
+                damage_events.send(DamageEvent {
+                    target,
+                    amount: damage,
+                    position: xform.translation.truncate(),
+                    crit: false
+                });
+            }
 
             // Mark the snow tile as used.
             commands.entity(entity).insert(DidDamage);
-
-            // Play enemy hit sound.
-            commands.spawn((
-                OnInGameScreen,
-                AudioBundle {
-                    source: asset_server.load("enemy_hit.ogg"),
-                    settings: PlaybackSettings {
-                        mode: bevy::audio::PlaybackMode::Despawn,
-                        speed: 0.25,
-                        volume: bevy::audio::Volume::Relative(bevy::audio::VolumeLevel::new(0.2)),
-                        ..default()
-                    },
-                    ..default()
-                },
-                EnemyWasHitSound,
-            ));
+            stats.hit();
+            adaptive.record_hit();
         }
     }
 }
 
-fn collide_projectile_with_player(
+fn collide_snow_with_enemy(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    player: Query<Entity, With<PlayerCapsule>>,
-    mut player_health: Query<&mut PlayerHealth>,
-    mut collisions: Query<(Entity, &CollidingEntities), (With<EnemyProjectile>, Without<DidDamage>)>
+    mut rng: ResMut<GameRng>,
+    mut enemies: Query<(&mut EnemyHealth, Option<&mut EnemyShield>), With<EnemyCapsule>>,
+    mut collisions: Query<(Entity, &CollidingEntities, &Transform), (With<SnowTile>, Without<DidDamage>)>,
+    mut damage_events: EventWriter<DamageEvent>,
+    (mut stats, mut adaptive): (ResMut<MatchStats>, ResMut<AdaptiveDifficulty>)
 ) {
-    for (entity, colliding_entities) in &mut collisions {
-        if colliding_entities.contains(&player.single())
+    for (entity, colliding_entities, xform) in &mut collisions {
+        // A snow tile only damages the first enemy it touches.
+        let hit = colliding_entities.iter().find(|e| enemies.contains(**e)).copied();
+        if let Some((target, Ok((mut health, shield)))) = hit.map(|e| (e, enemies.get_mut(e)))
         {
-            let max_hp = 100.0;
-            let mut rng = rand::thread_rng();
-            let dmg_factor: f32 = rng.gen_range(0.05..0.15);
-            let damage: f32 = max_hp * dmg_factor;
-            player_health.single_mut().0 -= damage;
+            let mut damage: f32 = rng.0.gen_range(1.0..5.0);
+            // Debugging... let damage: f32 = rng.gen_range(10.0..20.0);
 
-            // Mark the projectile as used.
-            commands.entity(entity).insert(DidDamage);
+            // Now and then a hit crits for double damage.
+            let crit = rng.0.gen_bool(0.1);
+            if crit {
+                damage *= 2.0;
+            }
+            // Shields soak up damage first.
+            if let Some(mut shield) = shield {
+                let absorbed = damage.min(shield.0);
+                shield.0 -= absorbed;
+                damage -= absorbed;
+            }
+            health.0 -= damage;
+            // Sent even when a shield soaked it all up, so the hit still
+            // gets some feedback.
+            damage_events.send(DamageEvent {
+                target,
+                amount: damage,
+                position: xform.translation.truncate(),
+                crit
+            });
 
-            // Play player hit sound.
-            commands.spawn((
-                OnInGameScreen,
-                AudioBundle {
-                    source: asset_server.load("player_hit.ogg"),
-                    settings: PlaybackSettings {
-                        mode: bevy::audio::PlaybackMode::Despawn,
-                        volume: bevy::audio::Volume::Relative(bevy::audio::VolumeLevel::new(0.05)),
-                        ..default()
-                    },
-                    ..default()
-                },
-                PlayerWasHitSound,
-            ));
+            // Mark the snow tile as used.
+            commands.entity(entity).insert(DidDamage);
+            stats.hit();
+            adaptive.record_hit();
         }
     }
 }
 
-fn update_enemy_health_bar(
-    mut query: Query<&mut ProgressBar, With<Healthbar>>,
-    health_query: Query<&EnemyHealth>,
-    dt: Res<Time>,
-    mut app_state: ResMut<NextState<AppState>>
+// Bosses change phase every time they lose a quarter of their health.
+fn boss_phase(health: f32, max_health: f32) -> u32 {
+    ((1.0 - health / max_health).clamp(0.0, 1.0) * 4.0) as u32
+}
+
+// Part of the simulation, so the stats move on once per step and online they
+// get rolled back with the rest of the match. Launches, hits and dodges are
+// counted by the systems they happen in.
+fn track_match_stats(
+    time: Res<Time>,
+    mut stats: ResMut<MatchStats>,
+    mut damage: EventReader<DamageEvent>,
+    players: Query<(), With<PlayerCapsule>>,
+    enemies: Query<(), With<EnemyCapsule>>
 ) {
-    for mut healthbar in query.iter_mut() {
-        let health = health_query.single().0;
-        if health < healthbar.value {
-            healthbar.value -= (healthbar.max_value - health) * dt.delta_seconds();
-        }
-        if healthbar.value <= 0.0 {
-            app_state.set(AppState::Win);
+    stats.tick(time.delta_seconds());
+    for event in damage.read() {
+        if players.contains(event.target) {
+            stats.hurt(event.amount);
+        } else if enemies.contains(event.target) {
+            stats.deal(event.amount);
         }
     }
 }
 
-fn update_player_health_bar(
-    mut query: Query<&mut ProgressBar, With<PlayerHealthbar>>,
-    health_query: Query<&PlayerHealth>,
-    dt: Res<Time>,
-    mut app_state: ResMut<NextState<AppState>>
+// Played from the snow and projectiles the collision systems marked, not by
+// those systems, so a netplay rollback resimulating a hit doesn't play it
+// again. Anything the rollback respawned is skipped.
+fn play_hit_sounds(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    game_mode: Res<GameMode>,
+    launched: Query<Ref<SnowTile>, Added<LaunchedBy>>,
+    snow_hits: Query<Ref<SnowTile>, Added<DidDamage>>,
+    projectile_hits: Query<Ref<EnemyProjectile>, Added<DidDamage>>
 ) {
-    for mut healthbar in query.iter_mut() {
-        let health = health_query.single().0;
-        if health < healthbar.value {
-            healthbar.value -= (healthbar.max_value - health) * dt.delta_seconds();
-        }
-        if healthbar.value <= 0.0 {
-            app_state.set(AppState::Lose);
+    let sound = |source: &str, volume: f32, speed: f32| AudioBundle {
+        source: asset_server.load(source),
+        settings: PlaybackSettings {
+            mode: bevy::audio::PlaybackMode::Despawn,
+            volume: bevy::audio::Volume::Relative(bevy::audio::VolumeLevel::new(volume)),
+            speed,
+            ..default()
+        },
+        ..default()
+    };
+
+    // Ice hit sound with random speed.
+    for _ in launched.iter().filter(|snow| !snow.is_added()) {
+        let audio_speed: f32 = rand::thread_rng().gen_range(0.8..1.2);
+        commands.spawn((OnInGameScreen, sound("ice-hit-mix.ogg", 0.03, audio_speed), IceWasHitSound));
+    }
+    // In versus snow only ever hits the other player.
+    for _ in snow_hits.iter().filter(|snow| !snow.is_added()) {
+        if *game_mode == GameMode::Versus {
+            commands.spawn((OnInGameScreen, sound("player_hit.ogg", 0.05, 1.0), PlayerWasHitSound));
+        } else {
+            commands.spawn((OnInGameScreen, sound("enemy_hit.ogg", 0.2, 0.25), EnemyWasHitSound));
         }
     }
+    for _ in projectile_hits.iter().filter(|projectile| !projectile.is_added()) {
+        commands.spawn((OnInGameScreen, sound("player_hit.ogg", 0.05, 1.0), PlayerWasHitSound));
+    }
 }
 
-fn remove_snow(
-    mut commands: Commands,
-    snow: Query<(Entity, &Transform), (With<SnowTile>, With<ToDelete>)>
+// Endless mode systems, see endless.rs.
+fn track_endless_run(
+    time: Res<Time>,
+    mut run: ResMut<EndlessRun>,
+    mut damage: EventReader<DamageEvent>,
+    players: Query<(), With<PlayerCapsule>>,
+    enemies: Query<(), With<EnemyCapsule>>
 ) {
-    for (entity, transform) in &snow {
-        if transform.translation.x < -130.0 || transform.translation.x > 250.0 ||
-            transform.translation.y < -100.0 || transform.translation.y > 150.0
-        {
-            commands.entity(entity).despawn_recursive();
+    run.tick(time.delta_seconds());
+    for event in damage.read() {
+        if players.contains(event.target) {
+            run.hurt();
+        } else if enemies.contains(event.target) {
+            run.hit();
         }
     }
 }
 
-fn remove_enemy_projectiles(
+fn respawn_endless_bear(
+    mut run: ResMut<EndlessRun>,
+    mut bosses: Query<(&mut EnemyHealth, &EnemyMaxHealth, &mut Speed, Option<&mut EnemyAttack>, &Transform), With<BossEnemy>>,
+    mut shakes: EventWriter<ScreenShake>,
+    mut bursts: EventWriter<ParticleBurst>
+) {
+    for (mut health, max_health, mut speed, attack, xform) in &mut bosses {
+        if health.0 > 0.0 {
+            continue;
+        }
+        // Back to full health, but faster and throwing more often.
+        health.0 = max_health.0;
+        speed.0 *= BEAR_SPEED_UP;
+        if let Some(mut attack) = attack {
+            let interval = attack.duration().div_f32(BEAR_ATTACK_SPEED_UP);
+            attack.set_duration(interval);
+        }
+        run.bear_level += 1;
+        info!("endless bear back at level {}", run.bear_level);
+
+        shakes.send(ScreenShake(0.8));
+        bursts.send(ParticleBurst {
+            effect: teddy_fluff(),
+            position: xform.translation.truncate().extend(150.0),
+            count: 30
+        });
+    }
+}
+
+// Snow and projectiles speed up the longer the run goes. This overrides
+// the rates set from the console.
+fn ramp_endless(
+    run: Res<EndlessRun>,
+    difficulty: Res<Difficulty>,
+    mut snow_config: ResMut<SnowConfig>,
+    mut projectile_config: ResMut<ProjectileConfig>
+) {
+    let intensity = run.intensity();
+    snow_config.timer.set_duration(SNOW_INTERVAL.div_f32(intensity));
+    projectile_config.rate = difficulty.projectile_rate() * intensity;
+}
+
+fn update_endless_hud(
+    run: Res<EndlessRun>,
+    mut texts: Query<&mut Text, With<EndlessHud>>
+) {
+    for mut text in &mut texts {
+        let mut value = format!("{}  Score {}", endless::format_time(run.seconds), run.score());
+        if run.combo > 1 {
+            value += &format!("\nCombo x{}", run.combo);
+        }
+        text.sections[0].value = value;
+    }
+}
+
+// Time attack systems, see timeattack.rs.
+fn setup_time_attack(
     mut commands: Commands,
-    projectiles: Query<(Entity, &Transform), With<EnemyProjectile>>
+    asset_server: Res<AssetServer>,
+    bests: Res<PersonalBests>,
+    (difficulty, adaptive): (Res<Difficulty>, Res<AdaptiveDifficulty>)
+) {
+    let key = PersonalBestKey {
+        difficulty: *difficulty,
+        adaptive: adaptive.enabled
+    };
+    let best = bests.get(key);
+    let ghost = !best.ghost.is_empty();
+    commands.insert_resource(TimeAttackRun::new(key, best));
+
+    // The clock sits at the top middle of the game area.
+    commands.spawn((
+        OnInGameScreen,
+        Name::new("TimeAttackHud"),
+        LetterboxedUi,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                padding: UiRect::top(Val::Px(8.0)),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        }
+    ))
+    .with_children(|parent| {
+        let style = TextStyle {
+            font_size: 16.0,
+            color: TEXT_COLOR,
+            ..default()
+        };
+        parent.spawn((
+            TimeAttackHud,
+            TextBundle::from_sections([
+                TextSection::new("", TextStyle { font_size: 24.0, ..style.clone() }),
+                TextSection::new("", style)
+            ]).with_text_alignment(TextAlignment::Center)
+        ));
+    });
+
+    if !ghost {
+        return;
+    }
+    commands.spawn((
+        OnInGameScreen,
+        Name::new("Ghost"),
+        Ghost,
+        SpriteBundle {
+            texture: asset_server.load("player_pixel_1.png"),
+            sprite: Sprite {
+                color: Color::rgba(0.6, 0.8, 1.0, 0.35),
+                ..default()
+            },
+            // Just behind the player.
+            transform: Transform::from_xyz(0.0, 0.0, 90.0),
+            visibility: Visibility::Hidden,
+            ..default()
+        }
+    ));
+}
+
+fn track_time_attack(
+    time: Res<Time>,
+    mut run: ResMut<TimeAttackRun>,
+    mut bests: ResMut<PersonalBests>,
+    players: Query<&Transform, With<PlayerCapsule>>,
+    bosses: Query<(&EnemyHealth, &EnemyMaxHealth), With<BossEnemy>>
+) {
+    if run.finished() {
+        return;
+    }
+    let player = players.iter().next().map(|xform| xform.translation.truncate());
+    run.tick(time.delta_seconds(), player);
+
+    let Some((health, max_health)) = bosses.iter().next() else {
+        return;
+    };
+    let fraction = health.0 / max_health.0;
+    if run.update_health(fraction) && run.finished() {
+        if let Some(new_best) = run.finish() {
+            bests.set(run.key, new_best);
+            bests.save();
+        }
+    }
+}
+
+fn update_time_attack_hud(
+    run: Res<TimeAttackRun>,
+    mut texts: Query<&mut Text, With<TimeAttackHud>>
+) {
+    for mut text in &mut texts {
+        text.sections[0].value = timeattack::format_time(run.seconds);
+
+        // The latest split, green when ahead of the best and red behind.
+        let Some(split) = run.splits.len().checked_sub(1) else {
+            continue;
+        };
+        let mark = (SPLIT_MARKS[split] * 100.0) as u32;
+        let (delta, color) = match run.split_delta(split) {
+            Some(delta) => (
+                format!("  {}", timeattack::format_delta(delta)),
+                if delta < 0.0 { Color::rgb(0.3, 0.9, 0.4) } else { Color::rgb(1.0, 0.4, 0.4) }
+            ),
+            None => (String::new(), TEXT_COLOR)
+        };
+        text.sections[1].value = format!("\n{mark}%  {}{delta}", timeattack::format_time(run.splits[split]));
+        text.sections[1].style.color = color;
+    }
+}
+
+fn move_ghost(
+    run: Res<TimeAttackRun>,
+    mut ghosts: Query<(&mut Transform, &mut Visibility), With<Ghost>>
 ) {
-    for (entity, transform) in &projectiles {
+    for (mut xform, mut visibility) in &mut ghosts {
+        match run.best.ghost_at(run.seconds) {
+            Some(position) => {
+                xform.translation = position.extend(xform.translation.z);
+                *visibility = Visibility::Inherited;
+            },
+            None => *visibility = Visibility::Hidden
+        }
+    }
+}
+
+// Keeps score of how the players are doing for adaptive difficulty, see
+// difficulty.rs.
+fn adapt_difficulty(
+    time: Res<Time>,
+    mut adaptive: ResMut<AdaptiveDifficulty>,
+    mut damage: EventReader<DamageEvent>,
+    players: Query<(), With<PlayerCapsule>>
+) {
+    for event in damage.read() {
+        if players.contains(event.target) {
+            adaptive.record_damage(event.amount);
+        }
+    }
+    if adaptive.tick(time.delta_seconds()) {
+        info!("adaptive difficulty now at {:.1}x", adaptive.factor);
+    }
+}
+
+// Players getting hit shakes the screen. Hits on the bear freeze the game
+// for a moment, and knocking it into its next phase shakes the screen hard.
+fn camera_fx_on_damage(
+    mut damage: EventReader<DamageEvent>,
+    players: Query<(), With<PlayerCapsule>>,
+    bosses: Query<(&EnemyHealth, &EnemyMaxHealth), With<BossEnemy>>,
+    mut shakes: EventWriter<ScreenShake>,
+    mut hit_stops: EventWriter<HitStop>
+) {
+    for event in damage.read() {
+        if players.contains(event.target) {
+            shakes.send(ScreenShake(0.4));
+        }
+        if let Ok((health, max_health)) = bosses.get(event.target) {
+            hit_stops.send(HitStop(0.06));
+            if boss_phase(health.0, max_health.0) > boss_phase(health.0 + event.amount, max_health.0) {
+                shakes.send(ScreenShake(0.8));
+            }
+        }
+    }
+}
+
+fn collide_projectile_with_player(
+    mut commands: Commands,
+    mut rng: ResMut<GameRng>,
+    (difficulty, god_mode): (Res<Difficulty>, Res<GodMode>),
+    mut players: Query<&mut PlayerHealth, (With<PlayerCapsule>, Without<Downed>)>,
+    mut collisions: Query<(Entity, &CollidingEntities, &Transform), (With<EnemyProjectile>, Without<DidDamage>)>,
+    (mut damage_events, mut bursts): (EventWriter<DamageEvent>, EventWriter<ParticleBurst>)
+) {
+    for (entity, colliding_entities, xform) in &mut collisions {
+        // A projectile only damages the first player it touches.
+        let hit = colliding_entities.iter().find(|e| players.contains(**e)).copied();
+        if let Some((target, Ok(mut player_health))) = hit.map(|e| (e, players.get_mut(e)))
+        {
+            let max_hp = 100.0;
+            let dmg_factor: f32 = rng.0.gen_range(0.05..0.15);
+            let damage: f32 = max_hp * dmg_factor * difficulty.damage_factor();
+            if !god_mode.0 {
+                player_health.0 -= damage;
+                damage_events.send(DamageEvent {
+                    target,
+                    amount: damage,
+                    position: xform.translation.truncate(),
+                    crit: false
+                });
+            }
+            bursts.send(ParticleBurst {
+                effect: teddy_fluff(),
+                position: xform.translation.truncate().extend(150.0),
+                count: 10
+            });
+
+            // Mark the projectile as used.
+            commands.entity(entity).insert(DidDamage);
+        }
+    }
+}
+
+fn down_players(
+    mut commands: Commands,
+    mut players: Query<(Entity, &mut PlayerHealth), (With<PlayerCapsule>, Without<Downed>)>
+) {
+    for (entity, mut health) in &mut players {
+        if health.0 <= 0.0 {
+            health.0 = 0.0;
+            commands.entity(entity).insert(Downed::default());
+        }
+    }
+}
+
+fn revive_players(
+    mut commands: Commands,
+    time: Res<Time>,
+    helpers: Query<(&Transform, &PlayerInput), (With<PlayerCapsule>, Without<Downed>)>,
+    mut downed: Query<(Entity, &Transform, &mut PlayerHealth, &mut Downed)>
+) {
+    for (entity, xform, mut health, mut downed) in &mut downed {
+        // A partner has to stay close and keep holding launch.
+        let reviving = helpers.iter().any(|(helper_xform, input)| {
+            input.action &&
+                helper_xform.translation.distance(xform.translation) < REVIVE_RANGE
+        });
+        if reviving {
+            downed.revive += time.delta_seconds();
+        } else {
+            downed.revive = 0.0;
+        }
+
+        if downed.revive >= REVIVE_SECS {
+            health.0 = REVIVE_HEALTH;
+            commands.entity(entity).remove::<Downed>();
+        }
+    }
+}
+
+fn tint_players(
+    players: Query<(&PlayerTint, &Children, Option<&Downed>), With<PlayerCapsule>>,
+    // Flashing sprites get their tint back once the flash is over.
+    mut sprites: Query<&mut Sprite, (With<PlayerSprite>, Without<HitFlash>)>
+) {
+    for (tint, children, downed) in &players {
+        // Downed players are dimmed and brighten up as they get revived.
+        let brightness = match downed {
+            Some(downed) => 0.3 + 0.7 * (downed.revive / REVIVE_SECS).min(1.0),
+            None => 1.0
+        };
+        let color = tint.0 * brightness;
+        let mut player_sprites = sprites.iter_many_mut(children);
+        while let Some(mut sprite) = player_sprites.fetch_next() {
+            sprite.color = color.with_a(1.0);
+        }
+    }
+}
+
+fn win_on_boss_bars_emptied(
+    mut emptied: EventReader<ProgressBarEmptied>,
+    bars: Query<&ProgressBar, With<Healthbar>>,
+    game_mode: Res<GameMode>,
+    mut app_state: ResMut<NextState<AppState>>
+) {
+    // The match is won once every boss bar has drained. In endless the bear
+    // comes straight back instead.
+    let boss_emptied = emptied.read().any(|event| bars.contains(event.entity));
+    if boss_emptied && *game_mode != GameMode::Endless && bars.iter().all(|bar| bar.value <= 0.0) {
+        app_state.set(AppState::Win);
+    }
+}
+
+fn end_on_player_bars_emptied(
+    mut emptied: EventReader<ProgressBarEmptied>,
+    bars: Query<(&ProgressBar, &ProgressBarBinding<PlayerHealth>), With<PlayerHealthbar>>,
+    slots: Query<&PlayerSlot>,
+    game_mode: Res<GameMode>,
+    mut versus: ResMut<VersusMatch>,
+    mut app_state: ResMut<NextState<AppState>>
+) {
+    if !emptied.read().any(|event| bars.contains(event.entity)) {
+        return;
+    }
+
+    // In versus the round goes to whoever is still standing, checking both
+    // bars in case they emptied on the same frame.
+    if *game_mode == GameMode::Versus {
+        let mut down = [false; 2];
+        for (bar, binding) in &bars {
+            if let (true, Ok(slot)) = (bar.value <= 0.0, slots.get(binding.source)) {
+                down[slot.0] = true;
+            }
+        }
+        if versus.finish_round(down) {
+            app_state.set(AppState::RoundResults);
+        }
+        return;
+    }
+
+    // The match is lost once every player bar has drained. Endless runs
+    // always end like this, and go to their own results.
+    if bars.iter().all(|(bar, _)| bar.value <= 0.0) {
+        app_state.set(match *game_mode {
+            GameMode::Endless => AppState::EndlessResults,
+            _ => AppState::Lose
+        });
+    }
+}
+
+// Online the bars show predicted frames, which a rollback can still undo, so
+// the round is only decided once the frames that decided it are confirmed.
+fn end_round_on_confirmed_health(
+    netplay: Res<Netplay>,
+    players: Query<(&PlayerSlot, &PlayerHealth), With<PlayerCapsule>>,
+    mut versus: ResMut<VersusMatch>,
+    mut app_state: ResMut<NextState<AppState>>
+) {
+    let mut down = [false; 2];
+    match netplay.session.confirmed_state() {
+        Some(snapshot) => {
+            for player in &snapshot.players {
+                down[player.slot] = player.health <= 0.0;
+            }
+        }
+        None => {
+            for (slot, health) in &players {
+                down[slot.0] = health.0 <= 0.0;
+            }
+        }
+    }
+    if versus.finish_round(down) {
+        app_state.set(AppState::RoundResults);
+    }
+}
+
+fn remove_snow(
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    snow: Query<(Entity, &Transform), (With<SnowTile>, With<ToDelete>)>
+) {
+    // Versus snow flies both ways, so only clean it up once it's off screen.
+    let (min_x, max_x, max_y) = match *game_mode {
+        GameMode::Versus => (-250.0, 250.0, 200.0),
+        _ => (-130.0, 250.0, 150.0)
+    };
+    for (entity, transform) in &snow {
+        if transform.translation.x < min_x || transform.translation.x > max_x ||
+            transform.translation.y < -100.0 || transform.translation.y > max_y
+        {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn remove_enemies(
+    mut commands: Commands,
+    enemies: Query<(Entity, &EnemyHealth, &EnemyMovement, &Transform), (With<EnemyCapsule>, Without<BossEnemy>)>
+) {
+    for (entity, health, movement, transform) in &enemies {
+        // Fly-across enemies are done once they leave the screen.
+        let left_screen = *movement == EnemyMovement::FlyAcross &&
+            (transform.translation.x < -220.0 || transform.translation.x > 220.0);
+        if health.0 <= 0.0 || left_screen
+        {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn remove_enemy_projectiles(
+    mut commands: Commands,
+    projectiles: Query<(Entity, &Transform, Has<DidDamage>), With<EnemyProjectile>>,
+    mut bursts: EventWriter<ParticleBurst>,
+    mut stats: ResMut<MatchStats>
+) {
+    for (entity, transform, did_damage) in &projectiles {
         if transform.translation.y < -100.0
         {
             commands.entity(entity).despawn_recursive();
+            if !did_damage {
+                stats.dodge();
+            }
+
+            // Fluff flies up from where it lands on the ground.
+            bursts.send(ParticleBurst {
+                effect: teddy_fluff(),
+                position: Vec3::new(transform.translation.x, -88.0, 250.0),
+                count: 8
+            });
+        }
+    }
+}
+
+// Particle effects...
+
+// Powder kicked up when a tile gets launched.
+fn snow_puff() -> ParticleEffect {
+    ParticleEffect {
+        colors: vec![Color::WHITE, Color::rgb(0.85, 0.92, 1.0)],
+        size: (4.0, 1.0),
+        lifetime: (0.3, 0.5),
+        speed: (20.0, 60.0),
+        gravity: -60.0,
+        drag: 3.0,
+        ..default()
+    }
+}
+
+// Left behind by tiles while they fly.
+fn sparkle_trail() -> ParticleEffect {
+    ParticleEffect {
+        colors: vec![Color::WHITE, Color::rgb(0.7, 0.95, 1.0), Color::rgb(1.0, 1.0, 0.8)],
+        size: (2.0, 0.0),
+        lifetime: (0.25, 0.4),
+        speed: (0.0, 8.0),
+        ..default()
+    }
+}
+
+// Stuffing that bursts out of teddy projectiles when they land.
+fn teddy_fluff() -> ParticleEffect {
+    ParticleEffect {
+        colors: vec![Color::rgb(0.95, 0.88, 0.75), Color::rgb(0.8, 0.6, 0.4), Color::WHITE],
+        size: (3.0, 1.0),
+        lifetime: (0.5, 0.8),
+        speed: (20.0, 50.0),
+        direction: std::f32::consts::FRAC_PI_2,
+        spread: 1.2,
+        gravity: -80.0,
+        drag: 2.0
+    }
+}
+
+// Rains down over the whole Win screen.
+fn confetti() -> ParticleEffect {
+    ParticleEffect {
+        colors: vec![
+            Color::rgb(1.0, 0.3, 0.3),
+            Color::rgb(1.0, 0.85, 0.2),
+            Color::rgb(0.3, 0.9, 0.4),
+            Color::rgb(0.3, 0.6, 1.0),
+            Color::rgb(0.9, 0.4, 1.0)
+        ],
+        size: (6.0, 5.0),
+        lifetime: (4.0, 6.0),
+        speed: (40.0, 120.0),
+        direction: -std::f32::consts::FRAC_PI_2,
+        spread: 0.6,
+        gravity: -120.0,
+        drag: 0.8
+    }
+}
+
+// Systems that change the match state. Offline they run every frame in
+// Update, online the rollback session steps them at a fixed rate.
+fn simulation_systems() -> SystemConfigs {
+    (
+        move_player,
+        spawn_snow,
+        move_snow,
+        spawn_enemy_projectiles,
+        move_enemy_projectiles,
+        collide_snow_with_player,
+        collide_snow_with_enemy,
+        collide_projectile_with_player,
+        collide_snow_with_rival.run_if(resource_equals(GameMode::Versus)),
+        down_players,
+        revive_players.run_if(resource_equals(GameMode::Coop)),
+        remove_snow,
+        remove_enemies,
+        remove_enemy_projectiles,
+        track_match_stats
+    ).chain()
+    .into_configs()
+}
+
+// The endless mode systems that change the match rather than just show it.
+fn endless_systems() -> SystemConfigs {
+    (
+        track_endless_run,
+        respawn_endless_bear,
+        ramp_endless
+    ).chain()
+    .into_configs()
+}
+
+// Offline and not recording or playing back, the match moves on once a
+// frame.
+fn stepped_each_frame(
+    netplay: Option<Res<Netplay>>,
+    clock: Option<Res<ReplayClock>>
+) -> bool {
+    netplay.is_none() && clock.is_none()
+}
+
+fn run_physics(world: &mut World) {
+    world.run_schedule(PhysicsUpdate);
+}
+
+// Console commands that change the match, see console.rs.
+fn apply_console_commands(
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut god_mode: ResMut<GodMode>,
+    (mut snow_config, mut projectile_config): (Option<ResMut<SnowConfig>>, Option<ResMut<ProjectileConfig>>),
+    mut players: Query<&mut PlayerHealth>,
+    mut enemies: Query<(&mut EnemyHealth, &EnemyMaxHealth)>
+) {
+    let mut say = |line: String| output.send(ConsoleOutput(line));
+    for command in console_commands.read() {
+        match command {
+            ConsoleCommand::Health(HealthTarget::Players, value) => {
+                let value = value.min(100.0);
+                for mut health in &mut players {
+                    health.0 = value;
+                }
+                say(format!("set {} players to {value} hp", players.iter().count()));
+            },
+            ConsoleCommand::Health(HealthTarget::Enemies, value) => {
+                for (mut health, max_health) in &mut enemies {
+                    health.0 = value.min(max_health.0);
+                }
+                say(format!("set {} enemies to {value} hp or their max", enemies.iter().count()));
+            },
+            ConsoleCommand::God(on) => {
+                god_mode.0 = on.unwrap_or(!god_mode.0);
+                say(format!("god mode {}", if god_mode.0 { "on" } else { "off" }));
+            },
+            ConsoleCommand::SnowRate(seconds) => match snow_config.as_mut() {
+                Some(config) => {
+                    config.timer.set_duration(Duration::from_secs_f32(*seconds));
+                    say(format!("snow every {seconds}s"));
+                },
+                None => say("only during a match".into())
+            },
+            ConsoleCommand::ProjectileRate(rate) => match projectile_config.as_mut() {
+                Some(config) => {
+                    config.rate = *rate;
+                    say(format!("enemies attack at {rate}x"));
+                },
+                None => say("only during a match".into())
+            },
+            ConsoleCommand::State(new_state) => {
+                next_state.set(*new_state);
+                say(format!("going to {new_state:?}"));
+            },
+            _ => {}
+        }
+    }
+}
+
+fn spawn_from_console(
+    mut commands: Commands,
+    mut console_commands: EventReader<ConsoleCommand>,
+    mut output: EventWriter<ConsoleOutput>,
+    asset_server: Res<AssetServer>,
+    state: Res<State<AppState>>,
+    game_mode: Res<GameMode>,
+    enemies: Query<&Transform, With<EnemyCapsule>>
+) {
+    for command in console_commands.read() {
+        let ConsoleCommand::Spawn(what, count) = command else {
+            continue;
+        };
+        if *state.get() != AppState::InGame {
+            output.send(ConsoleOutput("only during a match".into()));
+            continue;
+        }
+        match what {
+            Spawnable::Snow => {
+                for i in 0..*count {
+                    // Stacked up where the snow normally drops.
+                    let (position, drift) = match *game_mode {
+                        GameMode::Versus => (Vec2::new(0.0, 100.0), if i % 2 == 0 { 1.0 } else { -1.0 }),
+                        _ => (Vec2::new(192.0, 0.0), -1.0)
+                    };
+                    commands.spawn(snow_tile(
+                        asset_server.load("snow_1.png"),
+                        Transform::from_xyz(position.x, position.y + 36.0 * i as f32, 300.0),
+                        drift
+                    ));
+                }
+                output.send(ConsoleOutput(format!("spawned {count} snow")));
+            },
+            Spawnable::Projectile => {
+                // Thrown by the enemies in turn.
+                let throwers: Vec<Transform> = enemies.iter().copied().collect();
+                if throwers.is_empty() {
+                    output.send(ConsoleOutput("no enemies to throw them".into()));
+                    continue;
+                }
+                for i in 0..*count as usize {
+                    commands.spawn(enemy_projectile(
+                        asset_server.load("enemy_projectile.png"),
+                        throwers[i % throwers.len()]
+                    ));
+                }
+                output.send(ConsoleOutput(format!("spawned {count} projectiles")));
+            }
+        }
+    }
+}
+
+// The match being recorded with --record.
+#[derive(Resource)]
+struct ReplayRecording(Replay);
+
+// The match being played back with --replay.
+#[derive(Resource)]
+struct ReplayPlayback {
+    replay: Replay,
+    frame: usize
+}
+
+// Set while recording or playing back. The match is then stepped at a fixed
+// rate by replay_step instead of once a frame, so it runs at the same speed
+// whatever the frame rate and plays back exactly as it was recorded.
+#[derive(Resource, Default)]
+struct ReplayClock {
+    // Real time not yet simulated.
+    accumulator: f32
+}
+
+fn setup_replay(
+    mut commands: Commands,
+    cli: Res<Cli>,
+    versus: Res<VersusMatch>,
+    net_config: Option<Res<NetConfig>>,
+    mut timestep: ResMut<PhysicsTimestep>,
+    (game_mode, difficulty, adaptive): (Res<GameMode>, Res<Difficulty>, Res<AdaptiveDifficulty>)
+) {
+    // Online matches are driven by the rollback session instead.
+    if versus.online && net_config.is_some() {
+        return;
+    }
+    let settings = ReplaySettings {
+        mode: *game_mode,
+        difficulty: *difficulty,
+        adaptive: adaptive.enabled
+    };
+    if let Some(path) = &cli.replay {
+        match Replay::load(path) {
+            // It would play out differently on other settings.
+            Ok(replay) if replay.settings != settings => {
+                error!(
+                    "not playing back {}, it was recorded on {:?} and this match is on {settings:?}",
+                    path.display(),
+                    replay.settings
+                );
+                fail_headless(&cli);
+                return;
+            },
+            Ok(replay) => {
+                info!("playing back {} frames from {}", replay.frames.len(), path.display());
+                commands.insert_resource(GameRng(StdRng::seed_from_u64(replay.seed)));
+                commands.insert_resource(ReplayPlayback { replay, frame: 0 });
+            },
+            Err(err) => {
+                error!("couldn't load replay {}: {err}", path.display());
+                fail_headless(&cli);
+                return;
+            }
+        }
+    } else if cli.record.is_some() {
+        let seed = cli.seed.unwrap_or_else(rand::random);
+        commands.insert_resource(GameRng(StdRng::seed_from_u64(seed)));
+        commands.insert_resource(ReplayRecording(Replay::new(seed, settings)));
+    } else {
+        return;
+    }
+    commands.insert_resource(ReplayClock::default());
+    // Each run of the physics schedule is exactly one replay frame.
+    *timestep = PhysicsTimestep::FixedOnce(NET_DT);
+}
+
+fn replay_step(world: &mut World) {
+    let real_delta = world.resource::<Time>().delta_seconds();
+    // Playing back takes one step a frame, since every frame is NET_DT.
+    let steps = if world.contains_resource::<ReplayPlayback>() {
+        1
+    } else {
+        let mut clock = world.resource_mut::<ReplayClock>();
+        // Catch up on real time, but don't spiral after a long hitch.
+        clock.accumulator = (clock.accumulator + real_delta).min(NET_DT * 4.0);
+        let steps = (clock.accumulator / NET_DT) as u32;
+        clock.accumulator -= steps as f32 * NET_DT;
+        steps
+    };
+
+    // The match systems see a fixed step instead of the frame time.
+    let mut step_time = Time::<()>::default();
+    step_time.advance_by(Duration::from_secs_f32(NET_DT));
+    let real_time = std::mem::replace(&mut *world.resource_mut::<Time>(), step_time);
+    for _ in 0..steps {
+        world.run_schedule(ReplayUpdate);
+        world.run_schedule(PhysicsUpdate);
+    }
+    *world.resource_mut::<Time>() = real_time;
+}
+
+// Swaps in the recorded inputs when playing back, or saves this step's
+// inputs when recording.
+fn replay_input(
+    recording: Option<ResMut<ReplayRecording>>,
+    playback: Option<ResMut<ReplayPlayback>>,
+    mut players: Query<(&PlayerSlot, &mut PlayerInput)>
+) {
+    if let Some(mut playback) = playback {
+        if playback.frame == playback.replay.frames.len() {
+            info!("replay finished");
+        }
+        // Nobody presses anything once the replay runs out.
+        let frame = playback.replay.frames.get(playback.frame).copied().unwrap_or_default();
+        playback.frame += 1;
+        for (slot, mut input) in &mut players {
+            let recorded = frame[slot.0.min(1)];
+            let new_input = PlayerInput {
+                left: recorded.left(),
+                right: recorded.right(),
+                action: recorded.action()
+            };
+            if *input != new_input {
+                *input = new_input;
+            }
+        }
+    }
+    if let Some(mut recording) = recording {
+        let mut frame = [NetInput::default(); 2];
+        for (slot, input) in &players {
+            frame[slot.0.min(1)] = NetInput::new(input.left, input.right, input.action);
+        }
+        recording.0.frames.push(frame);
+    }
+}
+
+// Headless runs are for QA and automated tests, so they quit once the replay
+// has run out or the match is over.
+fn exit_headless(
+    state: Res<State<AppState>>,
+    playback: Option<Res<ReplayPlayback>>,
+    mut exit: EventWriter<AppExit>
+) {
+    let replay_over = playback.is_some_and(|playback| playback.frame > playback.replay.frames.len());
+    let match_over = matches!(state.get(), AppState::Win | AppState::Lose | AppState::EndlessResults);
+    if replay_over || match_over {
+        info!("headless run done on {:?}", state.get());
+        exit.send(AppExit);
+    }
+}
+
+// A replay that can't be played back fails the run, with a non-zero status
+// a test script can check. AppExit can't carry one.
+fn fail_headless(cli: &Cli) {
+    if cli.headless {
+        std::process::exit(1);
+    }
+}
+
+fn finish_replay(
+    mut commands: Commands,
+    cli: Res<Cli>,
+    recording: Option<Res<ReplayRecording>>,
+    mut timestep: ResMut<PhysicsTimestep>
+) {
+    if let (Some(recording), Some(path)) = (recording, &cli.record) {
+        match recording.0.save(path) {
+            Ok(()) => info!("saved {} frames of replay to {}", recording.0.frames.len(), path.display()),
+            Err(err) => error!("couldn't save replay to {}: {err}", path.display())
+        }
+    }
+    commands.remove_resource::<ReplayRecording>();
+    commands.remove_resource::<ReplayPlayback>();
+    commands.remove_resource::<ReplayClock>();
+    *timestep = PhysicsTimestep::default();
+}
+
+// Online versus state for the current round.
+#[derive(Resource)]
+pub struct Netplay {
+    session: RollbackSession<NetSnapshot>,
+    transport: Box<dyn Transport>,
+    // Real time not yet simulated.
+    accumulator: f32,
+    desync_reported: bool
+}
+
+fn setup_netplay(
+    mut commands: Commands,
+    versus: Res<VersusMatch>,
+    net_config: Option<Res<NetConfig>>,
+    mut timestep: ResMut<PhysicsTimestep>
+) {
+    let (true, Some(config)) = (versus.online, net_config) else {
+        return;
+    };
+    let transport = match UdpTransport::bind(config.local, config.peer) {
+        Ok(transport) => transport,
+        Err(err) => {
+            error!("couldn't start netplay on {}: {err}", config.local);
+            return;
+        }
+    };
+    if let Ok(addr) = transport.local_addr() {
+        info!("netplay round {} on {addr} with {}", versus.round, config.peer);
+    }
+
+    // Both sides have to start the round from exactly the same state.
+    commands.insert_resource(GameRng(StdRng::seed_from_u64(versus.round as u64)));
+    commands.insert_resource(Netplay {
+        session: RollbackSession::new(config.slot, versus.round as u16),
+        transport: Box::new(transport),
+        accumulator: 0.0,
+        desync_reported: false
+    });
+    // Each run of the physics schedule is exactly one rollback frame.
+    *timestep = PhysicsTimestep::FixedOnce(NET_DT);
+}
+
+fn stop_netplay(
+    mut commands: Commands,
+    mut timestep: ResMut<PhysicsTimestep>
+) {
+    commands.remove_resource::<Netplay>();
+    *timestep = PhysicsTimestep::default();
+}
+
+fn netplay_step(world: &mut World) {
+    let real_delta = world.resource::<Time>().delta_seconds();
+    world.resource_scope(|world, mut netplay: Mut<Netplay>| {
+        let netplay = &mut *netplay;
+        while let Some(bytes) = netplay.transport.recv() {
+            if let Some(packet) = Packet::decode(&bytes) {
+                netplay.session.receive(&packet);
+            }
+        }
+
+        // The simulation systems see a fixed step instead of the frame time.
+        let mut step_time = Time::<()>::default();
+        step_time.advance_by(std::time::Duration::from_secs_f32(NET_DT));
+        let real_time = std::mem::replace(&mut *world.resource_mut::<Time>(), step_time);
+
+        // Catch up on real time, but don't spiral after a long hitch.
+        netplay.accumulator = (netplay.accumulator + real_delta).min(NET_DT * 4.0);
+        while netplay.accumulator >= NET_DT {
+            let local_slot = netplay.session.local_slot();
+            let mut players = world.query::<(&PlayerSlot, &PlayerInput)>();
+            let input = players
+                .iter(world)
+                .find(|(slot, _)| slot.0 == local_slot)
+                .map(|(_, input)| NetInput::new(input.left, input.right, input.action))
+                .unwrap_or_default();
+            netplay.session.add_local_input(input);
+
+            let result = netplay.session.advance(&mut NetWorld(&mut *world));
+            if result.resimulated > 0 {
+                debug!("netplay rolled back {} frames", result.resimulated);
+            }
+            // Too far ahead of the other player, wait for their inputs.
+            if !result.stepped {
+                netplay.accumulator = 0.0;
+                break;
+            }
+            netplay.accumulator -= NET_DT;
+        }
+        *world.resource_mut::<Time>() = real_time;
+
+        let packet = netplay.session.make_packet();
+        netplay.transport.send(&packet.encode());
+
+        if let (Some(frame), false) = (netplay.session.desync_frame(), netplay.desync_reported) {
+            error!("netplay desync at frame {frame}, now at {}", netplay.session.frame());
+            netplay.desync_reported = true;
+            world.spawn((
+                OnInGameScreen,
+                Name::new("DesyncText"),
+                TextBundle::from_section(
+                    "Desync!",
+                    TextStyle {
+                        font_size: 16.0,
+                        color: Color::rgb(1.0, 0.3, 0.3),
+                        ..default()
+                    }
+                )
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(8.0),
+                    left: Val::Px(8.0),
+                    ..default()
+                })
+            ));
+        }
+    });
+}
+
+// Lets the rollback session save, load and step the game world.
+struct NetWorld<'w>(&'w mut World);
+
+impl RollbackGame for NetWorld<'_> {
+    type State = NetSnapshot;
+
+    fn save(&mut self) -> NetSnapshot {
+        NetSnapshot::save(self.0)
+    }
+
+    fn load(&mut self, snapshot: &NetSnapshot) {
+        snapshot.load(self.0);
+    }
+
+    fn step(&mut self, inputs: [NetInput; 2]) {
+        let mut players = self.0.query::<(&PlayerSlot, &mut PlayerInput, Has<Downed>)>();
+        for (slot, mut input, downed) in players.iter_mut(self.0) {
+            let net_input = if downed { NetInput::default() } else { inputs[slot.0] };
+            *input = PlayerInput {
+                left: net_input.left(),
+                right: net_input.right(),
+                action: net_input.action()
+            };
+        }
+        self.0.run_schedule(NetplayUpdate);
+        self.0.run_schedule(PhysicsUpdate);
+    }
+
+    fn checksum(&self, snapshot: &NetSnapshot) -> u64 {
+        snapshot.checksum()
+    }
+}
+
+#[derive(Clone)]
+struct BodySnapshot {
+    // Snow and projectiles are respawned on load, so their contacts with
+    // each other are matched up by the entities they had when saved.
+    entity: Entity,
+    transform: Transform,
+    linear: Vec2,
+    angular: f32,
+    // The collision systems read what each body is touching before the next
+    // physics step, so it has to come back with the rest of the state.
+    colliding: Vec<Entity>
+}
+
+impl BodySnapshot {
+    fn save(entity: EntityRef) -> Self {
+        Self {
+            entity: entity.id(),
+            transform: entity.get::<Transform>().copied().unwrap_or_default(),
+            linear: entity.get::<LinearVelocity>().map_or(Vec2::ZERO, |v| v.0),
+            angular: entity.get::<AngularVelocity>().map_or(0.0, |v| v.0),
+            colliding: entity.get::<CollidingEntities>()
+                .map(|colliding| colliding.iter().copied().collect())
+                .unwrap_or_default()
+        }
+    }
+
+    fn load(&self, mut entity: EntityWorldMut) {
+        let angle = self.transform.rotation.to_euler(EulerRot::XYZ).2;
+        entity.insert((
+            self.transform,
+            Position(self.transform.translation.truncate()),
+            Rotation::from_radians(angle),
+            LinearVelocity(self.linear),
+            AngularVelocity(self.angular),
+            CollidingEntities(self.colliding.iter().copied().collect())
+        ));
+    }
+
+    fn hash(&self, checksum: &mut Checksum) {
+        let translation = self.transform.translation;
+        for value in [translation.x, translation.y, self.linear.x, self.linear.y, self.angular] {
+            checksum.write_f32(value);
+        }
+    }
+}
+
+#[derive(Clone)]
+struct PlayerSnapshot {
+    slot: usize,
+    health: f32,
+    downed: Option<f32>,
+    body: BodySnapshot
+}
+
+#[derive(Clone)]
+struct SnowSnapshot {
+    texture: Handle<Image>,
+    body: BodySnapshot,
+    drift: f32,
+    launched_by: Option<usize>,
+    to_delete: bool,
+    did_damage: bool
+}
+
+#[derive(Clone)]
+struct ProjectileSnapshot {
+    texture: Handle<Image>,
+    body: BodySnapshot,
+    did_damage: bool
+}
+
+// Everything that can change between two frames of an online match.
+#[derive(Clone)]
+struct NetSnapshot {
+    rng: StdRng,
+    snow_timer: Timer,
+    stats: MatchStats,
+    players: Vec<PlayerSnapshot>,
+    enemies: Vec<(Entity, f32, Option<Timer>)>,
+    snow: Vec<SnowSnapshot>,
+    projectiles: Vec<ProjectileSnapshot>
+}
+
+impl NetSnapshot {
+    fn save(world: &mut World) -> Self {
+        let mut players = world
+            .query_filtered::<(EntityRef, &PlayerSlot, &PlayerHealth, Option<&Downed>), With<PlayerCapsule>>()
+            .iter(world)
+            .map(|(entity, slot, health, downed)| PlayerSnapshot {
+                slot: slot.0,
+                health: health.0,
+                downed: downed.map(|downed| downed.revive),
+                body: BodySnapshot::save(entity)
+            })
+            .collect::<Vec<_>>();
+        players.sort_by_key(|player| player.slot);
+
+        let enemies = world
+            .query::<(Entity, &EnemyHealth, Option<&EnemyAttack>)>()
+            .iter(world)
+            .map(|(entity, health, attack)| (entity, health.0, attack.map(|attack| attack.0.clone())))
+            .collect();
+
+        let snow = world
+            .query_filtered::<(EntityRef, &Handle<Image>, &SnowDrift, Option<&LaunchedBy>), With<SnowTile>>()
+            .iter(world)
+            .map(|(entity, texture, drift, launched_by)| SnowSnapshot {
+                texture: texture.clone(),
+                body: BodySnapshot::save(entity),
+                drift: drift.0,
+                launched_by: launched_by
+                    .and_then(|launched_by| world.get::<PlayerSlot>(launched_by.0))
+                    .map(|slot| slot.0),
+                to_delete: entity.contains::<ToDelete>(),
+                did_damage: entity.contains::<DidDamage>()
+            })
+            .collect();
+
+        let projectiles = world
+            .query_filtered::<(EntityRef, &Handle<Image>), With<EnemyProjectile>>()
+            .iter(world)
+            .map(|(entity, texture)| ProjectileSnapshot {
+                texture: texture.clone(),
+                body: BodySnapshot::save(entity),
+                did_damage: entity.contains::<DidDamage>()
+            })
+            .collect();
+
+        Self {
+            rng: world.resource::<GameRng>().0.clone(),
+            snow_timer: world.resource::<SnowConfig>().timer.clone(),
+            stats: world.resource::<MatchStats>().clone(),
+            players,
+            enemies,
+            snow,
+            projectiles
+        }
+    }
+
+    fn load(&self, world: &mut World) {
+        world.resource_mut::<GameRng>().0 = self.rng.clone();
+        world.resource_mut::<SnowConfig>().timer = self.snow_timer.clone();
+        *world.resource_mut::<MatchStats>() = self.stats.clone();
+
+        let player_entities = world
+            .query::<(Entity, &PlayerSlot)>()
+            .iter(world)
+            .map(|(entity, slot)| (slot.0, entity))
+            .collect::<Vec<_>>();
+        let player_entity = |slot: usize| player_entities
+            .iter()
+            .find(|(player_slot, _)| *player_slot == slot)
+            .map(|(_, entity)| *entity);
+        for player in &self.players {
+            let Some(entity) = player_entity(player.slot) else {
+                continue;
+            };
+            let mut entity = world.entity_mut(entity);
+            entity.insert(PlayerHealth(player.health));
+            match player.downed {
+                Some(revive) => entity.insert(Downed { revive }),
+                None => entity.remove::<Downed>()
+            };
+            player.body.load(entity);
+        }
+
+        for (entity, health, attack) in &self.enemies {
+            let Some(mut entity) = world.get_entity_mut(*entity) else {
+                continue;
+            };
+            entity.insert(EnemyHealth(*health));
+            if let Some(attack) = attack {
+                entity.insert(EnemyAttack(attack.clone()));
+            }
+        }
+
+        // Snow and projectiles come and go, so respawn them from scratch.
+        let temporary = world
+            .query_filtered::<Entity, Or<(With<SnowTile>, With<EnemyProjectile>)>>()
+            .iter(world)
+            .collect::<Vec<_>>();
+        for entity in temporary {
+            world.despawn(entity);
+        }
+        let mut respawned = HashMap::new();
+        for snow in &self.snow {
+            let mut entity = world.spawn(snow_tile(snow.texture.clone(), snow.body.transform, snow.drift));
+            respawned.insert(snow.body.entity, entity.id());
+            if snow.to_delete {
+                entity.insert(ToDelete);
+            }
+            if snow.did_damage {
+                entity.insert(DidDamage);
+            }
+            if let Some(launcher) = snow.launched_by.and_then(player_entity) {
+                entity.insert(LaunchedBy(launcher));
+            }
+            snow.body.load(entity);
+        }
+        for projectile in &self.projectiles {
+            let mut entity = world.spawn(enemy_projectile(projectile.texture.clone(), projectile.body.transform));
+            respawned.insert(projectile.body.entity, entity.id());
+            if projectile.did_damage {
+                entity.insert(DidDamage);
+            }
+            projectile.body.load(entity);
+        }
+
+        // Point everyone's contacts at the respawned snow and projectiles.
+        let mut colliding = world.query::<&mut CollidingEntities>();
+        for mut colliding in colliding.iter_mut(world) {
+            let remapped = colliding.iter()
+                .map(|entity| respawned.get(entity).copied().unwrap_or(*entity))
+                .collect();
+            colliding.0 = remapped;
+        }
+    }
+
+    fn checksum(&self) -> u64 {
+        let mut checksum = Checksum::default();
+        checksum.write(&self.snow_timer.elapsed().as_nanos().to_le_bytes());
+        for player in &self.players {
+            checksum.write_u32(player.slot as u32);
+            checksum.write_f32(player.health);
+            checksum.write_f32(player.downed.unwrap_or(-1.0));
+            player.body.hash(&mut checksum);
+        }
+        for (_, health, _) in &self.enemies {
+            checksum.write_f32(*health);
+        }
+
+        // Entity order isn't the same on both sides, so these are added up
+        // instead of hashed in sequence.
+        let mut temporary = 0u64;
+        for snow in &self.snow {
+            let mut item = Checksum::default();
+            snow.body.hash(&mut item);
+            item.write_f32(snow.drift);
+            item.write(&[snow.to_delete as u8, snow.did_damage as u8, snow.launched_by.map_or(2, |slot| slot as u8)]);
+            temporary = temporary.wrapping_add(item.finish());
+        }
+        for projectile in &self.projectiles {
+            let mut item = Checksum::default();
+            projectile.body.hash(&mut item);
+            item.write(&[projectile.did_damage as u8]);
+            temporary = temporary.wrapping_add(item.finish());
         }
+        checksum.write(&temporary.to_le_bytes());
+        checksum.finish()
     }
 }
 
@@ -1415,6 +3756,8 @@ fn despawn_screen<T: Component>(
 fn setup_win_screen(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    run: Option<Res<TimeAttackRun>>,
+    stats: Option<Res<MatchStats>>
 ) {
     // Start music.
     commands.spawn((
@@ -1431,11 +3774,97 @@ fn setup_win_screen(
         WinMusic,
     ));
 
-    // Define the base button styles.
+    // Set up the button layout using nodes.
+    commands.spawn((
+        OnWinGameScreen,
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        }
+    ))
+    .with_children(|parent| {
+        // Title text.
+        parent.spawn(TextBundle::from_section(
+            "You Win!",
+            TextStyle {
+                font_size: 40.0,
+                color: TEXT_COLOR,
+                ..default()
+            }
+        ));
+        // Time attack's time and splits.
+        if let Some(run) = &run {
+            let splits: Vec<String> = run.splits.iter()
+                .zip(SPLIT_MARKS)
+                .map(|(split, mark)| format!("{}%  {}", (mark * 100.0) as u32, timeattack::format_time(*split)))
+                .collect();
+            let verdict = match run.best.time() {
+                _ if run.new_best => "New personal best!".to_string(),
+                Some(best) => format!("Best {}", timeattack::format_time(best)),
+                None => String::new()
+            };
+            parent.spawn(
+                TextBundle::from_section(
+                    format!("{}\n{verdict}\n\n{}", timeattack::format_time(run.seconds), splits.join("\n")),
+                    TextStyle {
+                        font_size: 24.0,
+                        color: TEXT_COLOR,
+                        ..default()
+                    }
+                )
+                .with_text_alignment(TextAlignment::Center)
+            );
+        }
+        // How the match went.
+        if let Some(stats) = &stats {
+            spawn_match_stats(parent, stats);
+        }
+        spawn_results_buttons(parent);
+    });
+    // Create camera to view the menu.
+    commands.spawn(Camera2dBundle::default()).insert(OnWinGameScreen);
+    commands.remove_resource::<TimeAttackRun>();
+
+    // Confetti rains down from just above the top of the screen.
+    commands.spawn((
+        OnWinGameScreen,
+        Name::new("Confetti"),
+        ParticleEmitter::new(confetti(), 60.0).with_area(Vec2::new(640.0, 0.0)),
+        SpatialBundle::from_transform(Transform::from_xyz(0.0, 250.0, 10.0))
+    ));
+}
+
+// The match statistics, under the title of the win and lose screens.
+fn spawn_match_stats(parent: &mut ChildBuilder, stats: &MatchStats) {
+    parent.spawn(
+        TextBundle::from_section(
+            stats.summary(),
+            TextStyle {
+                font_size: 18.0,
+                color: Color::rgb(0.8, 0.8, 0.8),
+                ..default()
+            }
+        )
+        .with_text_alignment(TextAlignment::Center)
+        .with_style(Style {
+            margin: UiRect::top(Val::Px(12.0)),
+            ..default()
+        })
+    );
+}
+
+// Play Again starts the same mode over without going through the menu.
+fn spawn_results_buttons(parent: &mut ChildBuilder) {
     let button_style = Style {
         width: Val::Px(250.0),
         height: Val::Px(65.0),
-        margin: UiRect::all(Val::Px(20.0)),
+        margin: UiRect::all(Val::Px(10.0)),
         justify_content: JustifyContent::Center,
         align_items: AlignItems::Center,
         ..default()
@@ -1445,10 +3874,37 @@ fn setup_win_screen(
         color: TEXT_COLOR,
         ..default()
     };
+    parent.spawn(NodeBundle::default())
+        .with_children(|parent| {
+            for (action, label) in [
+                (OtherButtonActions::PlayAgain, "Play Again"),
+                (OtherButtonActions::Back, "Main Menu")
+            ] {
+                parent.spawn((
+                    ButtonBundle {
+                        style: button_style.clone(),
+                        background_color: NORMAL_BUTTON.into(),
+                        ..default()
+                    },
+                    action
+                ))
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        label,
+                        button_text_style.clone()
+                    ));
+                });
+            }
+        });
+}
 
+fn setup_lose_screen(
+    mut commands: Commands,
+    stats: Option<Res<MatchStats>>
+) {
     // Set up the button layout using nodes.
     commands.spawn((
-        OnWinGameScreen,
+        OnLoseGameScreen,
         NodeBundle {
             style: Style {
                 width: Val::Percent(100.0),
@@ -1463,32 +3919,53 @@ fn setup_win_screen(
     .with_children(|parent| {
         // Title text.
         parent.spawn(TextBundle::from_section(
-            "You Win!",
-            button_text_style.clone()
-        ));
-        // Start button.
-        parent.spawn((
-            ButtonBundle {
-                style: button_style.clone(),
-                background_color: NORMAL_BUTTON.into(),
+            "You Lose!",
+            TextStyle {
+                font_size: 40.0,
+                color: TEXT_COLOR,
                 ..default()
-            },
-            OtherButtonActions::Back,
-        ))
-        .with_children(|parent| {
-            parent.spawn(TextBundle::from_section(
-                "Main Menu",
-                button_text_style.clone()
-            ));
-        });
+            }
+        ));
+        // How the match went.
+        if let Some(stats) = &stats {
+            spawn_match_stats(parent, stats);
+        }
+        spawn_results_buttons(parent);
     });
     // Create camera to view the menu.
-    commands.spawn(Camera2dBundle::default()).insert(OnWinGameScreen);
+    commands.spawn(Camera2dBundle::default()).insert(OnLoseGameScreen);
 }
 
-fn setup_lose_screen(
-    mut commands: Commands
+fn setup_round_results_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut versus: ResMut<VersusMatch>
 ) {
+    // Start music.
+    commands.spawn((
+        OnRoundResultsScreen,
+        AudioBundle {
+            source: asset_server.load("Sweet_Sunday_Grove.ogg"),
+            settings: PlaybackSettings {
+                mode: bevy::audio::PlaybackMode::Loop,
+                volume: bevy::audio::Volume::Relative(bevy::audio::VolumeLevel::new(0.05)),
+                ..default()
+            },
+            ..default()
+        },
+        WinMusic,
+    ));
+
+    let round = versus.round;
+    let match_winner = versus.match_winner();
+    let title = match (match_winner, versus.last_winner) {
+        (Some(slot), _) => format!("Player {} wins the match!", slot + 1),
+        (None, Some(slot)) => format!("Player {} wins round {round}!", slot + 1),
+        (None, None) => format!("Round {round} is a draw!")
+    };
+    let score = format!("P1  {} - {}  P2\n(Best of {})", versus.wins[0], versus.wins[1], versus.best_of);
+    versus.round += 1;
+
     // Define the base button styles.
     let button_style = Style {
         width: Val::Px(250.0),
@@ -1506,7 +3983,7 @@ fn setup_lose_screen(
 
     // Set up the button layout using nodes.
     commands.spawn((
-        OnLoseGameScreen,
+        OnRoundResultsScreen,
         NodeBundle {
             style: Style {
                 width: Val::Percent(100.0),
@@ -1521,79 +3998,248 @@ fn setup_lose_screen(
     .with_children(|parent| {
         // Title text.
         parent.spawn(TextBundle::from_section(
-            "You Lose!",
-            button_text_style.clone()
+            title,
+            TextStyle {
+                font_size: 32.0,
+                color: TEXT_COLOR,
+                ..default()
+            }
         ));
-        // Start button.
+        // Score text.
+        parent.spawn(
+            TextBundle::from_section(
+                score,
+                TextStyle {
+                    font_size: 24.0,
+                    color: TEXT_COLOR,
+                    ..default()
+                }
+            )
+            .with_text_alignment(TextAlignment::Center)
+        );
+        // Next round or back to the menu once the match is decided.
+        let (action, label) = match match_winner {
+            Some(_) => (OtherButtonActions::Back, "Main Menu"),
+            None => (OtherButtonActions::NextRound, "Next Round")
+        };
         parent.spawn((
             ButtonBundle {
                 style: button_style.clone(),
                 background_color: NORMAL_BUTTON.into(),
                 ..default()
             },
-            OtherButtonActions::Back,
+            action,
         ))
         .with_children(|parent| {
             parent.spawn(TextBundle::from_section(
-                "Main Menu",
+                label,
                 button_text_style.clone()
             ));
         });
     });
     // Create camera to view the menu.
-    commands.spawn(Camera2dBundle::default()).insert(OnLoseGameScreen);
+    commands.spawn(Camera2dBundle::default()).insert(OnRoundResultsScreen);
 }
 
-fn reset_player_health_bar(
-    mut hbar_query: Query<(&mut Visibility, &mut ProgressBar), (With<PlayerHealthbar>, Without<PlayerShadowbar>)>,
-    mut sbar_query: Query<(&mut Visibility, &mut ProgressBar), (With<PlayerShadowbar>, Without<PlayerHealthbar>)>
+fn setup_endless_results_screen(
+    mut commands: Commands,
+    run: Option<Res<EndlessRun>>,
+    mut leaderboard: ResMut<Leaderboard>
 ) {
-    for (mut v, mut hbar) in &mut hbar_query {
-        *v = Visibility::Visible;
-        hbar.value = 100.0;
-    }
+    // Put the run on the board, once.
+    let (summary, place) = match run {
+        Some(run) => {
+            let place = leaderboard.insert(LeaderboardCategory::Endless, LeaderboardEntry {
+                score: run.score(),
+                seconds: run.seconds,
+                peak_combo: run.peak_combo
+            });
+            leaderboard.save();
+            commands.remove_resource::<EndlessRun>();
+            let summary = format!(
+                "Survived {}  Score {}\nPeak combo x{}  Bear beaten {} times",
+                endless::format_time(run.seconds),
+                run.score(),
+                run.peak_combo,
+                run.bear_level
+            );
+            (summary, place)
+        },
+        None => (String::new(), None)
+    };
+    let title = match place {
+        Some(0) => "New best!".to_string(),
+        Some(place) => format!("#{} on the board!", place + 1),
+        None => "Snowed under!".to_string()
+    };
+    let board = leaderboard.top(LeaderboardCategory::Endless)
+        .take(5)
+        .enumerate()
+        .map(|(i, entry)| format!(
+            "{}.  {:>5}   {}   x{}",
+            i + 1,
+            entry.score,
+            endless::format_time(entry.seconds),
+            entry.peak_combo
+        ))
+        .collect::<Vec<_>>()
+        .join("\n");
 
-    for (mut v, mut sbar) in &mut sbar_query {
-        *v = Visibility::Visible;
-        sbar.value = 100.0;
-    }
+    // Set up the button layout using nodes.
+    commands.spawn((
+        OnEndlessResultsScreen,
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            ..default()
+        }
+    ))
+    .with_children(|parent| {
+        // Title text.
+        parent.spawn(TextBundle::from_section(
+            title,
+            TextStyle {
+                font_size: 32.0,
+                color: TEXT_COLOR,
+                ..default()
+            }
+        ));
+        // How the run went.
+        parent.spawn(
+            TextBundle::from_section(
+                summary,
+                TextStyle {
+                    font_size: 24.0,
+                    color: TEXT_COLOR,
+                    ..default()
+                }
+            )
+            .with_text_alignment(TextAlignment::Center)
+        );
+        // The best endless runs.
+        parent.spawn(
+            TextBundle::from_section(
+                board,
+                TextStyle {
+                    font_size: 20.0,
+                    color: Color::rgb(0.8, 0.8, 0.8),
+                    ..default()
+                }
+            )
+        );
+        spawn_results_buttons(parent);
+    });
+    // Create camera to view the menu.
+    commands.spawn(Camera2dBundle::default()).insert(OnEndlessResultsScreen);
 }
 
-fn reset_enemy_health_bar(
-    mut hbar_query: Query<(&mut Visibility, &mut ProgressBar), (With<Healthbar>, Without<Shadowbar>)>,
-    mut sbar_query: Query<(&mut Visibility, &mut ProgressBar), (With<Shadowbar>, Without<Healthbar>)>
-) {
-    for (mut v, mut hbar) in &mut hbar_query {
-        *v = Visibility::Visible;
-        hbar.value = 100.0;
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    for (mut v, mut sbar) in &mut sbar_query {
-        *v = Visibility::Visible;
-        sbar.value = 100.0;
-    }
-}
+    #[test]
+    fn versus_rounds_go_to_whoever_is_standing() {
+        let mut versus = VersusMatch::new(3);
+        assert!(!versus.finish_round([false, false]));
+        assert!(versus.finish_round([true, false]));
+        assert_eq!((versus.last_winner, versus.wins), (Some(1), [0, 1]));
 
-fn hide_player_health_bar(
-    mut hbar: Query<&mut Visibility, (With<PlayerHealthbar>, Without<PlayerShadowbar>)>,
-    mut sbar: Query<&mut Visibility, (With<PlayerShadowbar>, Without<PlayerHealthbar>)>,
-) {
-    for mut b in &mut hbar {
-        *b = Visibility::Hidden;
+        // Both down on the same frame is a draw.
+        assert!(versus.finish_round([true, true]));
+        assert_eq!((versus.last_winner, versus.wins), (None, [0, 1]));
+        assert_eq!(versus.match_winner(), None);
+
+        versus.finish_round([true, false]);
+        assert_eq!(versus.match_winner(), Some(1));
     }
-    for mut b in &mut sbar {
-        *b = Visibility::Hidden;
+
+    // Just what the netplay simulation needs: two players on the ground and
+    // snow coming down quickly, so there's plenty to collide.
+    fn versus_world() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+                MinimalPlugins,
+                AssetPlugin::default(),
+                PhysicsPlugins::new(PhysicsUpdate)
+            ))
+            .init_asset::<Image>()
+            .init_asset::<bevy::audio::AudioSource>()
+            .add_event::<DamageEvent>()
+            .add_event::<ParticleBurst>()
+            .insert_resource(GameMode::Versus)
+            .insert_resource(Difficulty::Normal)
+            .init_resource::<AdaptiveDifficulty>()
+            .init_resource::<MatchStats>()
+            .init_resource::<GodMode>()
+            .insert_resource(GameRng(StdRng::seed_from_u64(7)))
+            .insert_resource(SnowConfig {
+                timer: Timer::from_seconds(0.2, TimerMode::Repeating)
+            })
+            .insert_resource(ProjectileConfig { rate: 1.0 })
+            .insert_resource(PhysicsTimestep::FixedOnce(NET_DT))
+            .add_systems(NetplayUpdate, simulation_systems());
+
+        // Every step is one rollback frame, like in netplay_step.
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_secs_f32(NET_DT));
+        app.insert_resource(time);
+
+        app.world.spawn(player_capsule("PlayerEntity", 0, -96.0, 1.0));
+        app.world.spawn(player_capsule("PlayerTwoEntity", 1, 96.0, -1.0));
+        app.world.spawn((
+            TransformBundle::from_transform(Transform::from_xyz(0.0, -104.0, 200.0)),
+            RigidBody::Static,
+            Collider::cuboid(640.0, 32.0),
+            CollisionLayers::new([Layer::Ground], [Layer::Player, Layer::Snow])
+        ));
+        app
     }
-}
 
-fn hide_enemy_health_bar(
-    mut hbar: Query<&mut Visibility, (With<Healthbar>, Without<Shadowbar>)>,
-    mut sbar: Query<&mut Visibility, (With<Shadowbar>, Without<Healthbar>)>,
-) {
-    for mut b in &mut hbar {
-        *b = Visibility::Hidden;
+    // Both players run about and launch snow at each other.
+    fn versus_inputs(frame: u32) -> [NetInput; 2] {
+        [
+            NetInput::new(frame % 90 < 20, frame % 90 >= 60, frame % 30 < 10),
+            NetInput::new(frame % 70 >= 50, frame % 70 < 15, frame % 40 < 12)
+        ]
     }
-    for mut b in &mut sbar {
-        *b = Visibility::Hidden;
+
+    #[test]
+    fn rolled_back_frames_play_out_the_same() {
+        const FRAMES: usize = 600;
+        let mut straight = versus_world();
+        let mut game = NetWorld(&mut straight.world);
+        let mut checksums = Vec::new();
+        for frame in 0..FRAMES {
+            let snapshot = game.save();
+            checksums.push(game.checksum(&snapshot));
+            game.step(versus_inputs(frame as u32));
+        }
+
+        // Every few steps go back a few frames and simulate them again, like
+        // a late remote input would.
+        let mut rolled_back = versus_world();
+        let mut game = NetWorld(&mut rolled_back.world);
+        let mut snapshots = Vec::new();
+        let mut steps = 0;
+        while snapshots.len() < FRAMES {
+            let frame = snapshots.len();
+            let snapshot = game.save();
+            assert_eq!(game.checksum(&snapshot), checksums[frame], "desync at frame {frame}");
+            snapshots.push(snapshot);
+            game.step(versus_inputs(frame as u32));
+
+            steps += 1;
+            if steps % 7 == 0 && frame >= 5 {
+                let rollback_to = frame - 4;
+                game.load(&snapshots[rollback_to]);
+                snapshots.truncate(rollback_to);
+            }
+        }
     }
-}
\ No newline at end of file
+}
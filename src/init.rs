@@ -1,40 +1,252 @@
 use bevy::prelude::*;
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::audio::AudioPlugin;
 use bevy::render::settings::{Backends, WgpuSettings, RenderCreation};
 use bevy::render::RenderPlugin;
-use bevy::window::{PresentMode, WindowTheme, EnabledButtons};
+use bevy::window::{ExitCondition, PresentMode, WindowTheme, EnabledButtons, WindowResizeConstraints};
+use bevy::winit::WinitPlugin;
+use std::time::Duration;
+
+use crate::cli::Cli;
+use crate::display::GAME_SIZE;
+use crate::net::NET_DT;
+use crate::settings::Settings;
+
+// Picked with `--renderer <name>`, this environment variable or `renderer = <name>`
+// in settings.cfg, in that order.
+const RENDERER_ENV: &str = "LOTS_OF_SNOW_RENDERER";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Renderer {
+    // Whatever works best on this platform.
+    #[default]
+    Auto,
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+    // No rendering at all, for headless runs.
+    None
+}
+
+impl Renderer {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "auto" => Some(Renderer::Auto),
+            "vulkan" => Some(Renderer::Vulkan),
+            "metal" => Some(Renderer::Metal),
+            "dx12" => Some(Renderer::Dx12),
+            "gl" | "opengl" => Some(Renderer::Gl),
+            "none" => Some(Renderer::None),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Renderer::Auto => "auto",
+            Renderer::Vulkan => "vulkan",
+            Renderer::Metal => "metal",
+            Renderer::Dx12 => "dx12",
+            Renderer::Gl => "gl",
+            Renderer::None => "none"
+        }
+    }
+
+    fn backends(self) -> Option<Backends> {
+        match self {
+            Renderer::Auto => Some(default_backends()),
+            Renderer::Vulkan => Some(Backends::VULKAN),
+            Renderer::Metal => Some(Backends::METAL),
+            Renderer::Dx12 => Some(Backends::DX12),
+            Renderer::Gl => Some(Backends::GL),
+            Renderer::None => None
+        }
+    }
+}
+
+// What works best for us on each platform.
+fn default_backends() -> Backends {
+    if cfg!(target_os = "windows") {
+        Backends::VULKAN
+    } else if cfg!(target_os = "linux") {
+        Backends::GL
+    } else {
+        Backends::PRIMARY
+    }
+}
+
+// The renderer asked for and where it came from. Names that aren't
+// renderers are skipped with a warning.
+fn requested_renderer(
+    cli: Option<&str>,
+    env: Option<&str>,
+    settings: &Settings,
+    warnings: &mut Vec<String>
+) -> (Renderer, &'static str) {
+    for (name, source) in [(cli, "command line"), (env, RENDERER_ENV)] {
+        let Some(name) = name else {
+            continue;
+        };
+        match Renderer::from_name(name) {
+            Some(renderer) => return (renderer, source),
+            None => warnings.push(format!(
+                "Unknown renderer \"{name}\" from {source}, expected auto, vulkan, metal, dx12, gl or none"
+            ))
+        }
+    }
+    (settings.renderer, "settings")
+}
+
+// The backends to start the renderer with. Falls back to the platform
+// default, then anything, then GL when the requested one has no adapter.
+// None means no renderer, either because it was asked for or nothing works.
+fn choose_backends(requested: Renderer, has_adapter: impl Fn(Backends) -> bool) -> Option<Backends> {
+    let first = requested.backends()?;
+    let mut tried = Vec::new();
+    for backends in [first, default_backends(), Backends::PRIMARY, Backends::GL] {
+        if tried.contains(&backends) {
+            continue;
+        }
+        tried.push(backends);
+        if has_adapter(backends) {
+            return Some(backends);
+        }
+    }
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn has_adapter(backends: Backends) -> bool {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        ..default()
+    });
+    instance.enumerate_adapters(backends).next().is_some()
+}
+
+// The browser picks the adapter, so there's nothing to check.
+#[cfg(target_arch = "wasm32")]
+fn has_adapter(_: Backends) -> bool {
+    true
+}
 
 pub struct InitPlugin;
 
 impl Plugin for InitPlugin {
     fn build(&self, app: &mut App)
     {
-        let win_title = "Lots of Snow (Bevy Jam 4) | kftoons";
-        app.add_plugins(DefaultPlugins.set(
+        let cli = app.world.get_resource::<Cli>().cloned().unwrap_or_default();
+
+        // Logging isn't set up until DefaultPlugins is added, so hold on to
+        // the messages until then.
+        let mut warnings = cli.warnings.clone();
+        let env = std::env::var(RENDERER_ENV).ok();
+        let (requested, source) = if cli.headless {
+            (Renderer::None, "--headless")
+        } else {
+            requested_renderer(cli.renderer.as_deref(), env.as_deref(), &Settings::load(), &mut warnings)
+        };
+        let backends = choose_backends(requested, has_adapter);
+        // Rather than open a window that would stay black. Logging isn't up
+        // yet, so this goes straight to stderr.
+        if backends.is_none() && requested != Renderer::None {
+            for warning in &warnings {
+                eprintln!("{warning}");
+            }
+            eprintln!(
+                "No graphics adapter found for renderer {} (from {source}) or any fallback. \
+                Check your graphics drivers, or try another renderer with --renderer or {RENDERER_ENV}.",
+                requested.name()
+            );
+            std::process::exit(1);
+        }
+
+        // The default 640x480 is 2x.
+        let scale = cli.scale.unwrap_or(2) as f32;
+        let window = Window {
+            title: "Lots of Snow (Bevy Jam 4) | kftoons".to_string(),
+            enabled_buttons: EnabledButtons {
+                minimize: true,
+                maximize: true,
+                close: true
+            },
+            // The game scales up to fit, see display.rs.
+            resizable: true,
+            resolution: (GAME_SIZE.x as f32 * scale, GAME_SIZE.y as f32 * scale).into(),
+            resize_constraints: WindowResizeConstraints {
+                min_width: 320.0,
+                min_height: 240.0,
+                ..default()
+            },
+            present_mode: PresentMode::AutoVsync,
+            window_theme: Some(WindowTheme::Dark),
+            ..default()
+        };
+
+        let plugins = DefaultPlugins.set(
             RenderPlugin {
                 render_creation: RenderCreation::Automatic(WgpuSettings {
-                    #[cfg(target_os = "windows")]
-                    backends: Some(Backends::VULKAN),
-                    #[cfg(target_os = "linux")]
-                    backends: Some(Backends::GL),
+                    backends,
                     ..default()
                 }),
                 ..default()
             }).set(
                 WindowPlugin {
-                    primary_window: Some(Window {
-                        title: win_title.into(),
-                        enabled_buttons: EnabledButtons {
-                            minimize: true,
-                            maximize: false,
-                            close: true
-                        },
-                        resizable: false,
-                        resolution: (640., 480.).into(),
-                        present_mode: PresentMode::AutoVsync,
-                        window_theme: Some(WindowTheme::Dark),
-                        ..default()
-                    }),
+                    primary_window: (!cli.headless).then_some(window),
+                    // Headless runs have no window to close, and quit by themselves.
+                    exit_condition: if cli.headless { ExitCondition::DontExit } else { ExitCondition::OnAllClosed },
                     ..default()
-                }).set(ImagePlugin::default_nearest()));
+                }).set(ImagePlugin::default_nearest())
+            .set(AudioPlugin {
+                global_volume: GlobalVolume::new(if cli.mute || cli.headless { 0.0 } else { 1.0 }),
+                ..default()
+            });
+        if cli.headless {
+            // Without winit, something else has to keep the frames coming.
+            app.add_plugins((
+                plugins.disable::<WinitPlugin>(),
+                ScheduleRunnerPlugin::run_loop(Duration::from_secs_f32(NET_DT))
+            ));
+        } else {
+            app.add_plugins(plugins);
+        }
+
+        for warning in warnings {
+            warn!("{warning}");
+        }
+        match backends {
+            Some(backends) => info!("Renderer {} (from {source}), using backends {backends:?}", requested.name()),
+            None => info!("Rendering disabled (from {source})")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_line_beats_env_beats_settings() {
+        let settings = Settings { renderer: Renderer::Dx12, ..Settings::default() };
+        let mut warnings = Vec::new();
+
+        assert_eq!(requested_renderer(Some("vulkan"), Some("gl"), &settings, &mut warnings), (Renderer::Vulkan, "command line"));
+        assert_eq!(requested_renderer(Some("None"), None, &settings, &mut warnings).0, Renderer::None);
+        assert_eq!(requested_renderer(None, Some("gl"), &settings, &mut warnings).0, Renderer::Gl);
+        assert_eq!(requested_renderer(None, None, &settings, &mut warnings), (Renderer::Dx12, "settings"));
+        assert!(warnings.is_empty());
+
+        // Bad names fall through to the next source.
+        assert_eq!(requested_renderer(Some("potato"), None, &settings, &mut warnings).0, Renderer::Dx12);
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn falls_back_when_there_is_no_adapter() {
+        assert_eq!(choose_backends(Renderer::Metal, |_| true), Some(Backends::METAL));
+        assert_eq!(choose_backends(Renderer::Metal, |b| b == Backends::GL), Some(Backends::GL));
+        assert_eq!(choose_backends(Renderer::Vulkan, |_| false), None);
+        assert_eq!(choose_backends(Renderer::None, |_| true), None);
     }
 }
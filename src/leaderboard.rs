@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+
+// Best runs for the modes that keep score, saved next to the game like the
+// settings. Each line is `category = score seconds peak_combo`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LeaderboardCategory {
+    Endless
+}
+
+impl LeaderboardCategory {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "endless" => Some(LeaderboardCategory::Endless),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LeaderboardCategory::Endless => "endless"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LeaderboardEntry {
+    pub score: u32,
+    pub seconds: f32,
+    pub peak_combo: u32
+}
+
+// Runs kept per category.
+pub const MAX_ENTRIES: usize = 10;
+
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct Leaderboard {
+    entries: Vec<(LeaderboardCategory, LeaderboardEntry)>
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+const LEADERBOARD_PATH: &str = "leaderboard.cfg";
+
+impl Leaderboard {
+    // Best first.
+    pub fn top(&self, category: LeaderboardCategory) -> impl Iterator<Item = &LeaderboardEntry> {
+        self.entries.iter()
+            .filter(move |(entry_category, _)| *entry_category == category)
+            .map(|(_, entry)| entry)
+    }
+
+    // Adds a run, returning its place (from 0) if it made the board.
+    pub fn insert(&mut self, category: LeaderboardCategory, entry: LeaderboardEntry) -> Option<usize> {
+        // Ties go to the run that got there first.
+        let place = self.top(category).take_while(|other| other.score >= entry.score).count();
+        if place >= MAX_ENTRIES {
+            return None;
+        }
+        let index = self.entries.iter()
+            .position(|(other_category, other)| *other_category == category && other.score < entry.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(index, (category, entry));
+
+        // Drop whoever got pushed off the bottom.
+        if let Some((last, _)) = self.entries.iter().enumerate()
+            .filter(|(_, (other_category, _))| *other_category == category)
+            .nth(MAX_ENTRIES) {
+            self.entries.remove(last);
+        }
+        Some(place)
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut leaderboard = Leaderboard::default();
+        for line in text.lines() {
+            // Bad lines are skipped, like in the settings file.
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let Some(category) = LeaderboardCategory::from_name(key.trim()) else {
+                continue;
+            };
+            let mut fields = value.split_whitespace();
+            let (Some(Ok(score)), Some(Ok(seconds)), Some(Ok(peak_combo))) = (
+                fields.next().map(str::parse),
+                fields.next().map(str::parse),
+                fields.next().map(str::parse)
+            ) else {
+                continue;
+            };
+            leaderboard.insert(category, LeaderboardEntry { score, seconds, peak_combo });
+        }
+        leaderboard
+    }
+
+    pub fn to_text(&self) -> String {
+        self.entries.iter()
+            .map(|(category, entry)| format!(
+                "{} = {} {} {}\n",
+                category.name(),
+                entry.score,
+                entry.seconds,
+                entry.peak_combo
+            ))
+            .collect()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> Self {
+        std::fs::read_to_string(LEADERBOARD_PATH)
+            .map(|text| Leaderboard::parse(&text))
+            .unwrap_or_default()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load() -> Self {
+        Leaderboard::default()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self) {
+        if let Err(err) = std::fs::write(LEADERBOARD_PATH, self.to_text()) {
+            warn!("Couldn't save the leaderboard to {LEADERBOARD_PATH}: {err}");
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&self) {}
+}
+
+pub struct LeaderboardPlugin;
+
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Leaderboard::load());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: u32) -> LeaderboardEntry {
+        LeaderboardEntry {
+            score,
+            seconds: score as f32 / 2.0,
+            peak_combo: score % 7
+        }
+    }
+
+    #[test]
+    fn keeps_the_best_runs_in_order() {
+        let mut leaderboard = Leaderboard::default();
+        assert_eq!(leaderboard.insert(LeaderboardCategory::Endless, entry(50)), Some(0));
+        assert_eq!(leaderboard.insert(LeaderboardCategory::Endless, entry(80)), Some(0));
+        assert_eq!(leaderboard.insert(LeaderboardCategory::Endless, entry(50)), Some(2));
+        for score in 100..110 {
+            leaderboard.insert(LeaderboardCategory::Endless, entry(score));
+        }
+        // Full up, so the low scores fell off and can't get back on.
+        assert_eq!(leaderboard.insert(LeaderboardCategory::Endless, entry(99)), None);
+        let scores: Vec<u32> = leaderboard.top(LeaderboardCategory::Endless).map(|entry| entry.score).collect();
+        assert_eq!(scores, (100..110).rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn round_trips_and_skips_bad_lines() {
+        let mut leaderboard = Leaderboard::default();
+        leaderboard.insert(LeaderboardCategory::Endless, entry(120));
+        leaderboard.insert(LeaderboardCategory::Endless, LeaderboardEntry { score: 7, seconds: 3.5, peak_combo: 2 });
+        assert_eq!(Leaderboard::parse(&leaderboard.to_text()), leaderboard);
+
+        let parsed = Leaderboard::parse("endless = 12 6 1\nendless = lots 1 1\nversus = 1 2 3\nnonsense");
+        assert_eq!(parsed.top(LeaderboardCategory::Endless).count(), 1);
+    }
+}
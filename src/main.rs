@@ -1,17 +1,52 @@
 use bevy::prelude::*;
 use bevy::asset::AssetMetaCheck;
 
+mod camerafx;
+mod cli;
+mod console;
+mod damagefx;
+#[cfg(feature = "debug")]
+mod debug;
+mod difficulty;
+mod display;
+mod endless;
 mod init;
+mod enemy;
 mod game;
+mod leaderboard;
+mod net;
+mod particles;
 mod progressbar;
+mod replay;
+mod settings;
+mod stats;
+mod timeattack;
 
 fn main() {
+    let cli = cli::Cli::from_args();
+    if cli.help {
+        print!("{}", cli::USAGE);
+        return;
+    }
+
     App::new()
         .insert_resource(AssetMetaCheck::Never)
+        // Read by the plugins as they're built.
+        .insert_resource(cli)
         .add_plugins((
             init::InitPlugin,
+            settings::SettingsPlugin,
+            camerafx::CameraFxPlugin,
+            console::ConsolePlugin,
+            damagefx::DamageFxPlugin,
+            display::DisplayPlugin,
             game::GamePlugin,
-            progressbar::ProgressBarPlugin
+            leaderboard::LeaderboardPlugin,
+            net::NetPlugin,
+            particles::ParticlesPlugin,
+            progressbar::ProgressBarPlugin,
+            #[cfg(feature = "debug")]
+            debug::DebugPlugin
         ))
         .run();
 }
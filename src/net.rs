@@ -0,0 +1,715 @@
+// Rollback netcode for online versus.
+//
+// Each side simulates every frame right away using its own input and a
+// guess for the remote input. When the real remote input arrives and the
+// guess was wrong, the game state is restored from a snapshot and the
+// frames are simulated again. Inputs are sent redundantly over UDP so a
+// lost packet is covered by the next one.
+
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+use crate::cli::Cli;
+
+// Fixed simulation step shared by both sides.
+pub const NET_DT: f32 = 1.0 / 60.0;
+
+// One player's buttons for one frame, packed into a byte.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NetInput(pub u8);
+
+impl NetInput {
+    pub const LEFT: u8 = 1 << 0;
+    pub const RIGHT: u8 = 1 << 1;
+    pub const ACTION: u8 = 1 << 2;
+
+    pub fn new(left: bool, right: bool, action: bool) -> Self {
+        let mut bits = 0;
+        if left {
+            bits |= Self::LEFT;
+        }
+        if right {
+            bits |= Self::RIGHT;
+        }
+        if action {
+            bits |= Self::ACTION;
+        }
+        Self(bits)
+    }
+
+    pub fn left(self) -> bool {
+        self.0 & Self::LEFT != 0
+    }
+
+    pub fn right(self) -> bool {
+        self.0 & Self::RIGHT != 0
+    }
+
+    pub fn action(self) -> bool {
+        self.0 & Self::ACTION != 0
+    }
+}
+
+// Everything one side tells the other each frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet {
+    // Bumped every round so stale packets from the last round are ignored.
+    pub round: u16,
+    // Frame of the first entry in `inputs`.
+    pub start_frame: u32,
+    pub inputs: Vec<NetInput>,
+    // Every remote input up to and including this frame has been received.
+    pub ack: Option<u32>,
+    // Checksum of the confirmed game state at the start of a frame.
+    pub checksum: Option<(u32, u64)>
+}
+
+const PACKET_MAGIC: u16 = 0x534e;
+// Upper bound on inputs resent per packet.
+const MAX_INPUTS_PER_PACKET: usize = 64;
+
+impl Packet {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(32 + self.inputs.len());
+        bytes.extend_from_slice(&PACKET_MAGIC.to_le_bytes());
+        bytes.extend_from_slice(&self.round.to_le_bytes());
+        bytes.extend_from_slice(&self.start_frame.to_le_bytes());
+        bytes.push(self.inputs.len() as u8);
+        bytes.extend(self.inputs.iter().map(|input| input.0));
+        bytes.extend_from_slice(&self.ack.unwrap_or(u32::MAX).to_le_bytes());
+        match self.checksum {
+            Some((frame, checksum)) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&frame.to_le_bytes());
+                bytes.extend_from_slice(&checksum.to_le_bytes());
+            },
+            None => bytes.push(0)
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = ByteReader { bytes };
+        if u16::from_le_bytes(reader.take()?) != PACKET_MAGIC {
+            return None;
+        }
+        let round = u16::from_le_bytes(reader.take()?);
+        let start_frame = u32::from_le_bytes(reader.take()?);
+        let [count] = reader.take()?;
+        let inputs = (0..count)
+            .map(|_| reader.take().map(|[bits]| NetInput(bits)))
+            .collect::<Option<Vec<_>>>()?;
+        let ack = match u32::from_le_bytes(reader.take()?) {
+            u32::MAX => None,
+            frame => Some(frame)
+        };
+        let checksum = match reader.take()? {
+            [1] => Some((u32::from_le_bytes(reader.take()?), u64::from_le_bytes(reader.take()?))),
+            _ => None
+        };
+        Some(Self { round, start_frame, inputs, ack, checksum })
+    }
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8]
+}
+
+impl ByteReader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        if self.bytes.len() < N {
+            return None;
+        }
+        let (head, tail) = self.bytes.split_at(N);
+        self.bytes = tail;
+        head.try_into().ok()
+    }
+}
+
+// Where packets go. UDP in the game, a simulated link in tests.
+pub trait Transport: Send + Sync {
+    fn send(&mut self, bytes: &[u8]);
+    fn recv(&mut self) -> Option<Vec<u8>>;
+}
+
+pub struct UdpTransport {
+    socket: UdpSocket,
+    peer: SocketAddr
+}
+
+impl UdpTransport {
+    pub fn bind(local: SocketAddr, peer: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind(local)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket, peer })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, bytes: &[u8]) {
+        // Dropped sends are covered by the redundant inputs in later packets.
+        if let Err(err) = self.socket.send_to(bytes, self.peer) {
+            if err.kind() != io::ErrorKind::WouldBlock {
+                warn!("netplay send failed: {err}");
+            }
+        }
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        let mut buffer = [0u8; 1024];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                // Ignore anything that isn't from our peer.
+                Ok((len, from)) if from == self.peer => return Some(buffer[..len].to_vec()),
+                Ok(_) => continue,
+                Err(_) => return None
+            }
+        }
+    }
+}
+
+// A game the rollback session can save, load and step.
+pub trait RollbackGame {
+    type State;
+
+    fn save(&mut self) -> Self::State;
+    fn load(&mut self, state: &Self::State);
+    // Simulate one frame with the inputs for player slots 0 and 1.
+    fn step(&mut self, inputs: [NetInput; 2]);
+    fn checksum(&self, state: &Self::State) -> u64;
+}
+
+// FNV-1a, so both sides agree on checksums no matter how they were built.
+#[derive(Clone, Copy)]
+pub struct Checksum(u64);
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Checksum {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.write(&value.to_bits().to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn finish(self) -> u64 {
+        self.0
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AdvanceResult {
+    // Frames that had to be simulated again because of a bad prediction.
+    pub resimulated: u32,
+    // False when too far ahead of the remote side and waiting for it.
+    pub stepped: bool
+}
+
+pub struct RollbackSession<S> {
+    local_slot: usize,
+    round: u16,
+    // The next frame to simulate.
+    frame: u32,
+    input_delay: u32,
+    max_prediction: u32,
+    local_inputs: BTreeMap<u32, NetInput>,
+    remote_inputs: BTreeMap<u32, NetInput>,
+    // Remote inputs guessed for frames that were simulated before they arrived.
+    predictions: BTreeMap<u32, NetInput>,
+    // Every remote input up to and including this frame has arrived.
+    confirmed: Option<u32>,
+    // Every local input up to and including this frame has been acked.
+    remote_ack: Option<u32>,
+    rollback_to: Option<u32>,
+    // Game state at the start of each frame that may still be rolled back.
+    snapshots: BTreeMap<u32, S>,
+    checksums: BTreeMap<u32, u64>,
+    remote_checksums: BTreeMap<u32, u64>,
+    desync: Option<u32>
+}
+
+impl<S> RollbackSession<S> {
+    pub fn new(local_slot: usize, round: u16) -> Self {
+        let input_delay = 2;
+        Self {
+            local_slot,
+            round,
+            frame: 0,
+            input_delay,
+            max_prediction: 8,
+            // Nobody can press anything during the input delay.
+            local_inputs: (0..input_delay).map(|frame| (frame, NetInput::default())).collect(),
+            remote_inputs: BTreeMap::new(),
+            predictions: BTreeMap::new(),
+            confirmed: None,
+            remote_ack: None,
+            rollback_to: None,
+            snapshots: BTreeMap::new(),
+            checksums: BTreeMap::new(),
+            remote_checksums: BTreeMap::new(),
+            desync: None
+        }
+    }
+
+    pub fn local_slot(&self) -> usize {
+        self.local_slot
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    // The game state at the start of this frame is final: every input
+    // before it has arrived and been simulated, so no rollback can change it.
+    pub fn confirmed_frame(&self) -> u32 {
+        let confirmed_next = self.confirmed.map_or(0, |frame| frame + 1);
+        let frame = confirmed_next.min(self.frame);
+        self.rollback_to.map_or(frame, |rollback_to| frame.min(rollback_to))
+    }
+
+    // The saved state at the start of the confirmed frame. None when that's
+    // the current frame, and the game's own state is the final one.
+    pub fn confirmed_state(&self) -> Option<&S> {
+        self.snapshots.get(&self.confirmed_frame())
+    }
+
+    // The first frame whose checksum didn't match the remote side.
+    pub fn desync_frame(&self) -> Option<u32> {
+        self.desync
+    }
+
+    // Queue this frame's local input. It takes effect after the input delay.
+    pub fn add_local_input(&mut self, input: NetInput) {
+        let frame = self.frame + self.input_delay;
+        self.local_inputs.entry(frame).or_insert(input);
+    }
+
+    pub fn receive(&mut self, packet: &Packet) {
+        if packet.round != self.round {
+            return;
+        }
+
+        for (offset, input) in packet.inputs.iter().enumerate() {
+            let frame = packet.start_frame + offset as u32;
+            if self.remote_inputs.contains_key(&frame) || self.is_confirmed(frame) {
+                continue;
+            }
+            self.remote_inputs.insert(frame, *input);
+
+            // Roll back to the earliest frame that was simulated with a wrong guess.
+            if let Some(predicted) = self.predictions.remove(&frame) {
+                if predicted != *input {
+                    self.rollback_to = Some(self.rollback_to.map_or(frame, |earliest| earliest.min(frame)));
+                }
+            }
+        }
+        let mut next = self.confirmed.map_or(0, |frame| frame + 1);
+        while self.remote_inputs.contains_key(&next) {
+            self.confirmed = Some(next);
+            next += 1;
+        }
+
+        if let Some(ack) = packet.ack {
+            self.remote_ack = Some(self.remote_ack.map_or(ack, |current| current.max(ack)));
+        }
+        if let Some((frame, checksum)) = packet.checksum {
+            self.remote_checksums.insert(frame, checksum);
+            self.compare_checksum(frame);
+        }
+    }
+
+    pub fn make_packet(&self) -> Packet {
+        // Resend every local input the remote side hasn't acked yet.
+        let first_unacked = self.remote_ack.map_or(0, |frame| frame + 1);
+        let inputs = self.local_inputs
+            .range(first_unacked..)
+            .take(MAX_INPUTS_PER_PACKET)
+            .map(|(_, input)| *input)
+            .collect();
+        Packet {
+            round: self.round,
+            start_frame: first_unacked,
+            inputs,
+            ack: self.confirmed,
+            checksum: self.checksums.iter().next_back().map(|(frame, checksum)| (*frame, *checksum))
+        }
+    }
+
+    pub fn advance<G: RollbackGame<State = S>>(&mut self, game: &mut G) -> AdvanceResult {
+        let mut result = AdvanceResult::default();
+
+        // Fix up any frames that were simulated with a wrong guess.
+        if let Some(rollback_to) = self.rollback_to.take() {
+            if rollback_to < self.frame {
+                if let Some(snapshot) = self.snapshots.get(&rollback_to) {
+                    game.load(snapshot);
+                    let current = self.frame;
+                    self.frame = rollback_to;
+                    while self.frame < current {
+                        self.step(game);
+                        result.resimulated += 1;
+                    }
+                }
+            }
+        }
+
+        // Don't run too far ahead of what the remote side has sent.
+        let confirmed_next = self.confirmed.map_or(0, |frame| frame + 1);
+        let has_local_input = self.local_inputs.contains_key(&self.frame);
+        if has_local_input && self.frame < confirmed_next + self.max_prediction {
+            self.step(game);
+            result.stepped = true;
+        }
+
+        self.trim();
+        result
+    }
+
+    fn step<G: RollbackGame<State = S>>(&mut self, game: &mut G) {
+        let frame = self.frame;
+        let snapshot = game.save();
+
+        // Once every input before this frame is confirmed its state is final.
+        let final_state = frame == 0 || self.is_confirmed(frame - 1);
+        if final_state && !self.checksums.contains_key(&frame) {
+            self.checksums.insert(frame, game.checksum(&snapshot));
+            self.compare_checksum(frame);
+        }
+        self.snapshots.insert(frame, snapshot);
+
+        let local = self.local_inputs.get(&frame).copied().unwrap_or_default();
+        let remote = match self.remote_inputs.get(&frame) {
+            Some(input) => *input,
+            None => {
+                // Guess the remote player is still holding what they last held.
+                let guess = self.remote_inputs
+                    .range(..frame)
+                    .next_back()
+                    .map(|(_, input)| *input)
+                    .unwrap_or_default();
+                self.predictions.insert(frame, guess);
+                guess
+            }
+        };
+
+        let mut inputs = [NetInput::default(); 2];
+        inputs[self.local_slot] = local;
+        inputs[1 - self.local_slot] = remote;
+        game.step(inputs);
+        self.frame += 1;
+    }
+
+    fn is_confirmed(&self, frame: u32) -> bool {
+        self.confirmed.is_some_and(|confirmed| frame <= confirmed)
+    }
+
+    fn compare_checksum(&mut self, frame: u32) {
+        if self.desync.is_some() {
+            return;
+        }
+        if let (Some(local), Some(remote)) = (self.checksums.get(&frame), self.remote_checksums.get(&frame)) {
+            if local != remote {
+                self.desync = Some(frame);
+            }
+        }
+    }
+
+    fn trim(&mut self) {
+        // Snapshots at or before the confirmed frame can never be rolled back to.
+        if let Some(confirmed) = self.confirmed {
+            self.snapshots.retain(|frame, _| *frame > confirmed);
+            self.remote_inputs.retain(|frame, _| *frame + 1 >= confirmed);
+        }
+        if let Some(ack) = self.remote_ack {
+            self.local_inputs.retain(|frame, _| *frame > ack);
+        }
+        // Keep a couple of seconds of checksums around to compare against.
+        let keep_from = self.frame.saturating_sub(120);
+        self.checksums.retain(|frame, _| *frame >= keep_from);
+        self.remote_checksums.retain(|frame, _| *frame >= keep_from);
+    }
+}
+
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        // Online versus is only offered when a peer was given.
+        if let Some(config) = app.world.get_resource::<Cli>().and_then(|cli| cli.net.clone()) {
+            app.insert_resource(config);
+        }
+    }
+}
+
+// How to reach the other player, from the command line (see cli.rs):
+//   --net-local 127.0.0.1:7000 --net-peer 127.0.0.1:7001 --net-slot 0
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct NetConfig {
+    pub local: SocketAddr,
+    pub peer: SocketAddr,
+    pub slot: usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::collections::VecDeque;
+
+    // One direction of a simulated network link with latency and packet loss.
+    // Time only moves when `tick` is called, one tick per game frame.
+    struct LossyLink {
+        latency: u32,
+        loss: f64,
+        rng: StdRng,
+        now: u32,
+        in_flight: VecDeque<(u32, Vec<u8>)>
+    }
+
+    impl LossyLink {
+        fn new(latency: u32, loss: f64, seed: u64) -> Self {
+            Self {
+                latency,
+                loss,
+                rng: StdRng::seed_from_u64(seed),
+                now: 0,
+                in_flight: VecDeque::new()
+            }
+        }
+
+        fn push(&mut self, bytes: &[u8]) {
+            if self.rng.gen_bool(self.loss) {
+                return;
+            }
+            // A little jitter on top of the base latency.
+            let jitter = self.rng.gen_range(0..=self.latency / 2);
+            self.in_flight.push_back((self.now + self.latency + jitter, bytes.to_vec()));
+        }
+
+        fn pop(&mut self) -> Option<Vec<u8>> {
+            let index = self.in_flight.iter().position(|(arrives, _)| *arrives <= self.now)?;
+            self.in_flight.remove(index).map(|(_, bytes)| bytes)
+        }
+
+        fn tick(&mut self) {
+            self.now += 1;
+        }
+    }
+
+
+    // A tiny deterministic game: two players walking along a line and
+    // scoring points while holding action.
+    #[derive(Default)]
+    struct ToyGame {
+        positions: [i32; 2],
+        scores: [u32; 2],
+        frame: u32,
+        // Lets a test make one side disagree with the other.
+        drift: i32
+    }
+
+    impl RollbackGame for ToyGame {
+        type State = ([i32; 2], [u32; 2], u32);
+
+        fn save(&mut self) -> Self::State {
+            (self.positions, self.scores, self.frame)
+        }
+
+        fn load(&mut self, state: &Self::State) {
+            (self.positions, self.scores, self.frame) = *state;
+        }
+
+        fn step(&mut self, inputs: [NetInput; 2]) {
+            for (slot, input) in inputs.iter().enumerate() {
+                if input.left() {
+                    self.positions[slot] -= 1;
+                }
+                if input.right() {
+                    self.positions[slot] += 1 + self.drift;
+                }
+                if input.action() {
+                    self.scores[slot] += self.positions[slot].unsigned_abs() % 7;
+                }
+            }
+            self.frame += 1;
+        }
+
+        fn checksum(&self, state: &Self::State) -> u64 {
+            let mut checksum = Checksum::default();
+            for position in state.0 {
+                checksum.write(&position.to_le_bytes());
+            }
+            for score in state.1 {
+                checksum.write_u32(score);
+            }
+            checksum.write_u32(state.2);
+            checksum.finish()
+        }
+    }
+
+    // Scripted input that changes often enough to force mispredictions.
+    fn scripted_input(slot: usize, frame: u32) -> NetInput {
+        let phase = (frame / (5 + slot as u32 * 3)) % 4;
+        NetInput::new(phase == 1, phase == 2 || phase == 3, frame % 11 < 4)
+    }
+
+    struct Peer {
+        session: RollbackSession<([i32; 2], [u32; 2], u32)>,
+        game: ToyGame
+    }
+
+    // Run two peers over a simulated link. Each side stops pressing buttons
+    // after `frames` frames, which leaves both simulations at `frames + 2`
+    // once the input delay has played out.
+    fn run_loopback(frames: u32, latency: u32, loss: f64, drift: i32) -> (Peer, Peer) {
+        let mut peers = [
+            Peer { session: RollbackSession::new(0, 1), game: ToyGame::default() },
+            Peer { session: RollbackSession::new(1, 1), game: ToyGame { drift, ..default() } },
+        ];
+        let mut links = [LossyLink::new(latency, loss, 1), LossyLink::new(latency, loss, 2)];
+
+        // Keep going after the last input so everything gets delivered.
+        for _ in 0..frames + 200 {
+            for (slot, peer) in peers.iter_mut().enumerate() {
+                while let Some(bytes) = links[1 - slot].pop() {
+                    peer.session.receive(&Packet::decode(&bytes).unwrap());
+                }
+                let frame = peer.session.frame();
+                if frame < frames {
+                    peer.session.add_local_input(scripted_input(slot, frame + 2));
+                }
+                peer.session.advance(&mut peer.game);
+                links[slot].push(&peer.session.make_packet().encode());
+            }
+            links.iter_mut().for_each(LossyLink::tick);
+        }
+        let [a, b] = peers;
+        (a, b)
+    }
+
+    #[test]
+    fn packet_round_trips() {
+        let packet = Packet {
+            round: 3,
+            start_frame: 42,
+            inputs: vec![NetInput::new(true, false, true), NetInput::default()],
+            ack: Some(40),
+            checksum: Some((39, 0xdead_beef))
+        };
+        assert_eq!(Packet::decode(&packet.encode()), Some(packet));
+        assert_eq!(Packet::decode(&[0, 1, 2]), None);
+    }
+
+    #[test]
+    fn prediction_miss_rolls_back() {
+        let mut session = RollbackSession::new(0, 0);
+        let mut game = ToyGame::default();
+        for _ in 0..4 {
+            session.add_local_input(NetInput::default());
+            session.advance(&mut game);
+        }
+        // The remote player was holding right the whole time.
+        session.receive(&Packet {
+            round: 0,
+            start_frame: 0,
+            inputs: vec![NetInput::new(false, true, false); 4],
+            ack: None,
+            checksum: None
+        });
+        session.add_local_input(NetInput::default());
+        let result = session.advance(&mut game);
+        assert_eq!(result.resimulated, 4);
+        assert_eq!(game.positions, [0, 5]);
+    }
+
+    #[test]
+    fn confirmed_state_ignores_predictions() {
+        let mut session = RollbackSession::new(0, 0);
+        let mut game = ToyGame::default();
+        for _ in 0..4 {
+            session.add_local_input(NetInput::default());
+            session.advance(&mut game);
+        }
+        assert_eq!(session.confirmed_frame(), 0);
+        assert_eq!(session.confirmed_state(), Some(&([0, 0], [0, 0], 0)));
+
+        let holding_right = |start_frame, frames| Packet {
+            round: 0,
+            start_frame,
+            inputs: vec![NetInput::new(false, true, false); frames],
+            ack: None,
+            checksum: None
+        };
+        // Nothing is final past a wrong guess until it's been resimulated.
+        session.receive(&holding_right(0, 2));
+        assert_eq!(session.confirmed_frame(), 0);
+        session.add_local_input(NetInput::default());
+        session.advance(&mut game);
+        assert_eq!(session.confirmed_frame(), 2);
+        assert_eq!(session.confirmed_state(), Some(&([0, 2], [0, 0], 2)));
+
+        // Once every input has arrived the current state is the final one.
+        session.receive(&holding_right(2, 3));
+        assert_eq!(session.confirmed_frame(), session.frame());
+        assert_eq!(session.confirmed_state(), None);
+        assert_eq!(game.positions, [0, 5]);
+    }
+
+    #[test]
+    fn peers_agree_over_lossy_link() {
+        let (a, b) = run_loopback(600, 4, 0.25, 0);
+        assert_eq!(a.session.frame(), 602);
+        assert_eq!(b.session.frame(), 602);
+        assert_eq!(a.game.positions, b.game.positions);
+        assert_eq!(a.game.scores, b.game.scores);
+        assert_eq!(a.session.desync_frame(), None);
+        assert_eq!(b.session.desync_frame(), None);
+    }
+
+    #[test]
+    fn mismatched_simulation_is_detected() {
+        let (a, b) = run_loopback(300, 2, 0.1, 1);
+        assert!(a.session.desync_frame().is_some() || b.session.desync_frame().is_some());
+    }
+
+    #[test]
+    fn udp_transport_on_localhost() {
+        let any: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let mut a = UdpTransport::bind(any, any).unwrap();
+        let mut b = UdpTransport::bind(any, a.local_addr().unwrap()).unwrap();
+        a.peer = b.local_addr().unwrap();
+
+        let packet = Packet {
+            round: 1,
+            start_frame: 0,
+            inputs: vec![NetInput::new(false, false, true)],
+            ack: None,
+            checksum: None
+        };
+        a.send(&packet.encode());
+        let received = (0..100).find_map(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            b.recv()
+        });
+        assert_eq!(received.and_then(|bytes| Packet::decode(&bytes)), Some(packet));
+    }
+}
@@ -0,0 +1,245 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+// How a batch of particles looks and moves. Ranges are (min, max) and each
+// particle picks its own value in them.
+#[derive(Clone)]
+pub struct ParticleEffect {
+    // Each particle gets one of these at random.
+    pub colors: Vec<Color>,
+    // Size at birth and at death.
+    pub size: (f32, f32),
+    pub lifetime: (f32, f32),
+    pub speed: (f32, f32),
+    // Angle particles fly off at, in radians, and how far either side of it
+    // they can stray.
+    pub direction: f32,
+    pub spread: f32,
+    pub gravity: f32,
+    // How quickly particles slow down, per second.
+    pub drag: f32
+}
+
+impl Default for ParticleEffect {
+    fn default() -> Self {
+        Self {
+            colors: vec![Color::WHITE],
+            size: (2.0, 0.0),
+            lifetime: (0.5, 0.5),
+            speed: (0.0, 0.0),
+            direction: 0.0,
+            spread: std::f32::consts::PI,
+            gravity: 0.0,
+            drag: 0.0
+        }
+    }
+}
+
+// Keeps emitting particles from wherever its entity is.
+#[derive(Component)]
+pub struct ParticleEmitter {
+    pub effect: ParticleEffect,
+    // Particles per second.
+    pub rate: f32,
+    // Particles start anywhere in this box around the emitter.
+    pub area: Vec2,
+    accumulator: f32
+}
+
+impl ParticleEmitter {
+    pub fn new(effect: ParticleEffect, rate: f32) -> Self {
+        Self {
+            effect,
+            rate,
+            area: Vec2::ZERO,
+            accumulator: 0.0
+        }
+    }
+
+    pub fn with_area(mut self, area: Vec2) -> Self {
+        self.area = area;
+        self
+    }
+}
+
+// Emits a one-off burst of particles.
+#[derive(Event, Clone)]
+pub struct ParticleBurst {
+    pub effect: ParticleEffect,
+    pub position: Vec3,
+    pub count: u32
+}
+
+#[derive(Component)]
+pub struct Particle {
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+    size: (f32, f32),
+    gravity: f32,
+    drag: f32
+}
+
+// Dead particles are hidden and kept here to be reused, so bursts don't
+// spawn and despawn entities every frame.
+#[derive(Resource, Default)]
+pub struct ParticlePool {
+    free: Vec<Entity>,
+    total: usize
+}
+
+const MAX_PARTICLES: usize = 1024;
+
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ParticleBurst>()
+            .init_resource::<ParticlePool>()
+            .add_systems(Update, (
+                    emit_bursts,
+                    run_emitters,
+                    update_particles
+                ).chain()
+            );
+    }
+}
+
+type ParticleQuery<'w, 's> = Query<'w, 's, (&'static mut Particle, &'static mut Sprite, &'static mut Transform, &'static mut Visibility)>;
+
+fn emit(
+    commands: &mut Commands,
+    pool: &mut ParticlePool,
+    particles: &mut ParticleQuery,
+    effect: &ParticleEffect,
+    position: Vec3
+) {
+    let mut rng = rand::thread_rng();
+    let angle = effect.direction + rng.gen_range(-effect.spread..=effect.spread);
+    let particle = Particle {
+        velocity: Vec2::from_angle(angle) * rng.gen_range(effect.speed.0..=effect.speed.1),
+        age: 0.0,
+        lifetime: rng.gen_range(effect.lifetime.0..=effect.lifetime.1).max(0.01),
+        size: effect.size,
+        gravity: effect.gravity,
+        drag: effect.drag
+    };
+    let color = effect.colors
+        .get(rng.gen_range(0..effect.colors.len().max(1)))
+        .copied()
+        .unwrap_or(Color::WHITE);
+    let sprite = Sprite {
+        color,
+        custom_size: Some(Vec2::splat(effect.size.0)),
+        ..default()
+    };
+
+    // Reuse a dead particle if there is one.
+    if let Some(entity) = pool.free.pop() {
+        if let Ok((mut old, mut old_sprite, mut transform, mut visibility)) = particles.get_mut(entity) {
+            *old = particle;
+            *old_sprite = sprite;
+            transform.translation = position;
+            *visibility = Visibility::Visible;
+            return;
+        }
+    }
+    if pool.total >= MAX_PARTICLES {
+        return;
+    }
+    pool.total += 1;
+    commands.spawn((
+        Name::new("Particle"),
+        particle,
+        SpriteBundle {
+            sprite,
+            transform: Transform::from_translation(position),
+            ..default()
+        }
+    ));
+}
+
+fn emit_bursts(
+    mut commands: Commands,
+    mut bursts: EventReader<ParticleBurst>,
+    mut pool: ResMut<ParticlePool>,
+    mut particles: ParticleQuery
+) {
+    // Recounted every frame in case something else despawned particles, so
+    // the limit doesn't fill up with ones that are gone.
+    pool.free.retain(|entity| particles.contains(*entity));
+    pool.total = particles.iter().count();
+
+    for burst in bursts.read() {
+        for _ in 0..burst.count {
+            emit(&mut commands, &mut pool, &mut particles, &burst.effect, burst.position);
+        }
+    }
+}
+
+fn run_emitters(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pool: ResMut<ParticlePool>,
+    mut emitters: Query<(&mut ParticleEmitter, &GlobalTransform)>,
+    mut particles: ParticleQuery
+) {
+    let mut rng = rand::thread_rng();
+    for (mut emitter, xform) in &mut emitters {
+        emitter.accumulator += emitter.rate * time.delta_seconds();
+        while emitter.accumulator >= 1.0 {
+            emitter.accumulator -= 1.0;
+            let half = emitter.area / 2.0;
+            let offset = Vec2::new(
+                rng.gen_range(-half.x..=half.x),
+                rng.gen_range(-half.y..=half.y)
+            );
+            let position = xform.translation() + offset.extend(0.0);
+            emit(&mut commands, &mut pool, &mut particles, &emitter.effect, position);
+        }
+    }
+}
+
+fn update_particles(
+    time: Res<Time>,
+    mut pool: ResMut<ParticlePool>,
+    mut particles: Query<(Entity, &mut Particle, &mut Sprite, &mut Transform, &mut Visibility)>
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut particle, mut sprite, mut transform, mut visibility) in &mut particles {
+        if *visibility == Visibility::Hidden {
+            continue;
+        }
+        particle.age += dt;
+        if particle.age >= particle.lifetime {
+            *visibility = Visibility::Hidden;
+            pool.free.push(entity);
+            continue;
+        }
+
+        particle.velocity.y += particle.gravity * dt;
+        let drag = (1.0 - particle.drag * dt).max(0.0);
+        particle.velocity *= drag;
+        transform.translation += (particle.velocity * dt).extend(0.0);
+
+        // Shrink and fade out over the particle's life.
+        let t = particle.age / particle.lifetime;
+        let (start, end) = particle.size;
+        sprite.custom_size = Some(Vec2::splat(start + (end - start) * t));
+        sprite.color.set_a(1.0 - t * t);
+    }
+}
+
+// Hides every particle and puts it back in the pool, for when the screen it
+// was flying over goes away.
+pub fn clear_particles(
+    mut pool: ResMut<ParticlePool>,
+    mut particles: Query<(Entity, &mut Visibility), With<Particle>>
+) {
+    pool.free.clear();
+    for (entity, mut visibility) in &mut particles {
+        *visibility = Visibility::Hidden;
+        pool.free.push(entity);
+    }
+    pool.total = pool.free.len();
+}
@@ -1,17 +1,31 @@
 // credit: https://github.com/CAOakleyII/bevy_health_bar
 
+use std::marker::PhantomData;
+
 use bevy::{
     app::{App, Plugin, Update},
     asset::{Assets, Handle},
     ecs::{
         bundle::Bundle,
         component::Component,
-        system::{Query, Res},
+        entity::Entity,
+        event::{Event, EventWriter},
+        query::{Added, Changed, With, Without},
+        removal_detection::RemovedComponents,
+        schedule::{IntoSystemConfigs, IntoSystemSetConfigs, SystemSet},
+        system::{Commands, Local, Query, Res},
+    },
+    hierarchy::{BuildChildren, Children, DespawnRecursiveExt, Parent},
+    math::{Rect, Vec2, Vec3},
+    render::{color::Color, texture::Image, view::Visibility},
+    sprite::{Anchor, Sprite, SpriteBundle},
+    time::Time,
+    transform::components::{GlobalTransform, Transform},
+    ui::{
+        node_bundles::{ImageBundle, NodeBundle},
+        BackgroundColor, Overflow, PositionType, Style, UiImage, Val,
     },
-    math::{Rect, Vec2},
-    render::texture::Image,
-    sprite::{Sprite, SpriteBundle},
-    transform::components::Transform,
+    utils::HashMap,
 };
 
 #[derive(Component, Default)]
@@ -19,15 +33,312 @@ pub struct ProgressBar {
     pub value: f32,
     pub max_value: f32,
     pub step: f32,
+    pub direction: FillDirection,
+}
+
+// Which way a bar fills up. The bar's transform sits on the edge it fills
+// from, or in the middle for `CenterOut`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FillDirection {
+    #[default]
+    LeftToRight,
+    RightToLeft,
+    BottomToTop,
+    TopToBottom,
+    CenterOut,
+}
+
+impl FillDirection {
+    fn anchor(self) -> Anchor {
+        match self {
+            FillDirection::LeftToRight => Anchor::CenterLeft,
+            FillDirection::RightToLeft => Anchor::CenterRight,
+            FillDirection::BottomToTop => Anchor::BottomCenter,
+            FillDirection::TopToBottom => Anchor::TopCenter,
+            FillDirection::CenterOut => Anchor::Center,
+        }
+    }
+
+    // The part of a `size` image that's shown at `percent` full.
+    fn visible_rect(self, size: Vec2, percent: f32) -> Rect {
+        let Vec2 { x: w, y: h } = size;
+        match self {
+            FillDirection::LeftToRight => Rect::new(0.0, 0.0, w * percent, h),
+            FillDirection::RightToLeft => Rect::new(w * (1.0 - percent), 0.0, w, h),
+            // Image rows go top to bottom.
+            FillDirection::BottomToTop => Rect::new(0.0, h * (1.0 - percent), w, h),
+            FillDirection::TopToBottom => Rect::new(0.0, 0.0, w, h * percent),
+            FillDirection::CenterOut => Rect::new(w * (1.0 - percent) / 2.0, 0.0, w * (1.0 + percent) / 2.0, h),
+        }
+    }
+
+    // Where the full bar sits relative to its transform.
+    fn local_rect(self, size: Vec2) -> Rect {
+        let anchor = self.anchor().as_vec();
+        Rect::from_center_size(-anchor * size, size)
+    }
+}
+
+impl ProgressBar {
+    // How many segments the bar is split into, when `step` is set.
+    pub fn segments(&self) -> Option<u32> {
+        (self.step > 0.0 && self.max_value > 0.0)
+            .then(|| (self.max_value / self.step).round().clamp(1.0, 100.0) as u32)
+    }
+
+    // How full the bar is, from 0 to 1.
+    pub fn percent(&self) -> f32 {
+        self.fraction(self.value)
+    }
+
+    // Over-filled bars are full. Bars without a usable max are empty rather
+    // than NaN, which would leave the sprite with a broken rect.
+    fn fraction(&self, value: f32) -> f32 {
+        if self.max_value.is_nan() || self.max_value <= 0.0 || value.is_nan() {
+            return 0.0;
+        }
+        f32::clamp(value / self.max_value, 0.0, 1.0)
+    }
+
+    // How full the bar looks at `value`, rounded up to whole segments.
+    fn shown_percent(&self, value: f32) -> f32 {
+        let percent = self.fraction(value);
+        match self.segments() {
+            // Nudged down so float error doesn't round a full segment up.
+            Some(segments) => ((percent * segments as f32) - 1e-4).ceil() / segments as f32,
+            None => percent,
+        }
+    }
 }
 
 #[derive(Bundle, Default)]
 pub struct ProgressBarBundle {
     pub progresss_bar: ProgressBar,
 
+    pub layers: ProgressBarLayers,
+
+    pub style: ProgressBarStyle,
+
     pub sprite_bundle: SpriteBundle,
 }
 
+// A bar laid out by bevy_ui instead of placed in the world. It shares the
+// value, layers, style and events of sprite bars, but frames are only drawn
+// on sprite bars.
+#[derive(Component, Clone, Default)]
+pub struct ProgressBarNode {
+    pub texture: Handle<Image>,
+    // Size of the bar on screen. The texture is stretched to fit.
+    pub size: Vec2,
+}
+
+#[derive(Bundle, Default)]
+pub struct ProgressBarNodeBundle {
+    pub progresss_bar: ProgressBar,
+
+    pub layers: ProgressBarLayers,
+
+    pub style: ProgressBarStyle,
+
+    pub node: ProgressBarNode,
+
+    pub node_bundle: NodeBundle,
+}
+
+// Keeps a sprite bar floating over another entity. The bar isn't a child of
+// the target, so it stays level while the target spins, and it's despawned
+// along with the target.
+#[derive(Component, Clone)]
+pub struct ProgressBarFollow {
+    pub target: Entity,
+    pub offset: Vec3,
+    // Hide the bar once it's been full for this many seconds. Bars that
+    // start full start hidden.
+    pub hide_when_full: Option<f32>,
+    full_for: f32,
+}
+
+impl ProgressBarFollow {
+    pub fn new(target: Entity, offset: Vec3) -> Self {
+        Self {
+            target,
+            offset,
+            hide_when_full: None,
+            // As if it had been full forever, until the value first drops.
+            full_for: f32::INFINITY,
+        }
+    }
+    pub fn with_hide_when_full(mut self, seconds: f32) -> Self {
+        self.hide_when_full = Some(seconds);
+        self
+    }
+}
+
+// Extra layers drawn behind a bar, like a fighting game health bar.
+#[derive(Component, Clone)]
+pub struct ProgressBarLayers {
+    // Full width backdrop that shows where the bar is empty.
+    pub background: Option<Color>,
+    // Lags behind the bar when it drops, then drains to catch up.
+    pub trail: Option<Color>,
+    // Jumps ahead of the bar when it rises, then the bar fills up to it.
+    pub heal: Option<Color>,
+    // Seconds before the trail or bar starts catching up.
+    pub delay: f32,
+    // Seconds it takes to catch up.
+    pub duration: f32,
+    // Border drawn around the whole bar.
+    pub frame: Option<NineSlice>,
+}
+
+// An image drawn around a bar with its corners and edges kept at their
+// original size, so only the middle stretches.
+#[derive(Clone)]
+pub struct NineSlice {
+    pub texture: Handle<Image>,
+    // Pixels from each edge of the image that make up the border.
+    pub border: f32,
+    // Gap between the bar and the inside of the border.
+    pub padding: f32,
+}
+
+impl Default for ProgressBarLayers {
+    fn default() -> Self {
+        Self {
+            background: None,
+            trail: None,
+            heal: None,
+            delay: 0.6,
+            duration: 0.5,
+            frame: None,
+        }
+    }
+}
+
+// How a bar is colored and split up.
+#[derive(Component, Clone)]
+pub struct ProgressBarStyle {
+    // (fraction, color) pairs from highest to lowest. The bar takes the
+    // color of the first one it's at or above, or the last one.
+    pub thresholds: Vec<(f32, Color)>,
+    // Pulse when at or below this fraction.
+    pub pulse_below: Option<f32>,
+    // Lines drawn between segments when `ProgressBar::step` is set.
+    pub divider: Color,
+    pub divider_width: f32,
+}
+
+impl Default for ProgressBarStyle {
+    fn default() -> Self {
+        Self {
+            thresholds: Vec::new(),
+            pulse_below: None,
+            divider: Color::BLACK,
+            divider_width: 1.0,
+        }
+    }
+}
+
+impl ProgressBarStyle {
+    // Green when healthy, yellow when hurt, and a pulsing red when low.
+    pub fn health() -> Self {
+        Self {
+            thresholds: vec![
+                (0.6, Color::rgb(0.2, 0.85, 0.3)),
+                (0.25, Color::rgb(0.95, 0.85, 0.2)),
+                (0.0, Color::rgb(0.9, 0.2, 0.2)),
+            ],
+            pulse_below: Some(0.25),
+            ..Default::default()
+        }
+    }
+
+    fn color(&self, percent: f32, elapsed_seconds: f32) -> Option<Color> {
+        let pulsing = self.pulse_below.is_some_and(|below| percent <= below);
+        if self.thresholds.is_empty() && !pulsing {
+            return None;
+        }
+        let base = self
+            .thresholds
+            .iter()
+            .find(|(threshold, _)| percent >= *threshold)
+            .or(self.thresholds.last())
+            .map_or(Color::WHITE, |(_, color)| *color);
+        if !pulsing {
+            return Some(base);
+        }
+        let brightness = 0.65 + 0.35 * (elapsed_seconds * 8.0).cos();
+        Some((base * brightness).with_a(base.a()))
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum ProgressBarLayer {
+    Background,
+    Trail,
+    // One of the nine frame pieces, numbered left to right, top to bottom.
+    Frame(usize),
+    // The line after segment n. Center-out bars need a second, mirrored one.
+    Divider(u32, bool),
+}
+
+// Marks the lines between a bar's segments, on sprite and UI bars alike.
+#[derive(Component)]
+struct ProgressBarDivider;
+
+// The segments and direction a bar's dividers were last built for.
+#[derive(Component, PartialEq)]
+struct ProgressBarDividers {
+    segments: u32,
+    direction: FillDirection,
+}
+
+type DividedBarQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static ProgressBar,
+        Option<&'static ProgressBarStyle>,
+        Option<&'static ProgressBarNode>,
+        Option<&'static ProgressBarDividers>,
+        Option<&'static Children>,
+    ),
+    Changed<ProgressBar>,
+>;
+
+// The nodes making up a UI bar. The fill is clipped so only part of the
+// image inside it shows.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum ProgressBarNodePart {
+    Trail,
+    Fill,
+    Image,
+}
+
+// What a bar with a trail or heal layer is currently showing.
+#[derive(Component)]
+struct ProgressBarDisplay {
+    front: f32,
+    trail: f32,
+    // Where the moving layer started from.
+    from: f32,
+    elapsed: f32,
+    healing: bool,
+}
+
+impl ProgressBarDisplay {
+    fn new(value: f32) -> Self {
+        Self {
+            front: value,
+            trail: value,
+            from: value,
+            elapsed: 0.0,
+            healing: false,
+        }
+    }
+}
+
 impl ProgressBarBundle {
     pub fn new(progress: f32, texture: Handle<Image>) -> Self {
         Self {
@@ -36,6 +347,8 @@ impl ProgressBarBundle {
                 max_value: progress,
                 ..Default::default()
             },
+            layers: Default::default(),
+            style: Default::default(),
             sprite_bundle: SpriteBundle {
                 texture,
                 sprite: Sprite {
@@ -50,22 +363,462 @@ impl ProgressBarBundle {
         self.sprite_bundle.transform = transform;
         self
     }
+    pub fn with_layers(mut self, layers: ProgressBarLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+}
+
+impl ProgressBarNodeBundle {
+    pub fn new(progress: f32, texture: Handle<Image>, size: Vec2) -> Self {
+        Self {
+            progresss_bar: ProgressBar {
+                value: progress,
+                max_value: progress,
+                ..Default::default()
+            },
+            node: ProgressBarNode { texture, size },
+            ..Default::default()
+        }
+    }
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.node_bundle.style = style;
+        self
+    }
+    pub fn with_layers(mut self, layers: ProgressBarLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+}
+
+// Anything a bar can show, like a health component.
+pub trait ProgressSource: Component {
+    fn progress(&self) -> f32;
+}
+
+// Keeps a bar in step with a `ProgressSource` component on another entity.
+// Register the source type with `App::add_progress_source` first.
+#[derive(Component)]
+pub struct ProgressBarBinding<T: ProgressSource> {
+    pub source: Entity,
+    marker: PhantomData<T>,
+}
+
+impl<T: ProgressSource> ProgressBarBinding<T> {
+    pub fn new(source: Entity) -> Self {
+        Self {
+            source,
+            marker: PhantomData,
+        }
+    }
+}
+
+// Sent whenever a bar's value changes.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ProgressBarChanged {
+    pub entity: Entity,
+    pub old: f32,
+    pub new: f32,
+}
+
+// Sent when a bar runs out.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ProgressBarEmptied {
+    pub entity: Entity,
+}
+
+// Sent when a bar fills all the way up.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ProgressBarFilled {
+    pub entity: Entity,
+}
+
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ProgressBarSet {
+    // Bound bars copy their source values.
+    Bind,
+    // Change events are sent and trails move.
+    Notify,
+    // Sprites and nodes are cropped to match.
+    Render,
+}
+
+pub trait ProgressBarAppExt {
+    fn add_progress_source<T: ProgressSource>(&mut self) -> &mut Self;
+}
+
+impl ProgressBarAppExt for App {
+    fn add_progress_source<T: ProgressSource>(&mut self) -> &mut Self {
+        self.add_systems(Update, ProgressBarPlugin::bind::<T>.in_set(ProgressBarSet::Bind))
+    }
 }
+
 pub struct ProgressBarPlugin;
 
 impl Plugin for ProgressBarPlugin {
     fn build(&self, app: &mut App) {
-        // Probably Want Event Stream for steps
-        app.add_systems(Update, Self::update);
+        app.add_event::<ProgressBarChanged>()
+            .add_event::<ProgressBarEmptied>()
+            .add_event::<ProgressBarFilled>()
+            .configure_sets(
+                Update,
+                (
+                    ProgressBarSet::Bind,
+                    ProgressBarSet::Notify,
+                    ProgressBarSet::Render,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    Self::spawn_layers,
+                    Self::spawn_nodes,
+                    // After the nodes, so UI dividers are drawn over the fill.
+                    Self::spawn_dividers.after(Self::spawn_layers).after(Self::spawn_nodes),
+                    Self::notify,
+                    Self::animate_layers,
+                )
+                    .in_set(ProgressBarSet::Notify),
+            )
+            .add_systems(
+                Update,
+                (
+                    Self::follow,
+                    Self::update,
+                    Self::update_colors,
+                    Self::update_layers,
+                    Self::update_nodes,
+                )
+                    .in_set(ProgressBarSet::Render),
+            );
     }
 }
 
 impl ProgressBarPlugin {
+    fn bind<T: ProgressSource>(
+        mut bars: Query<(&mut ProgressBar, &ProgressBarBinding<T>)>,
+        sources: Query<&T>,
+    ) {
+        for (mut progress_bar, binding) in bars.iter_mut() {
+            let Ok(source) = sources.get(binding.source) else {
+                continue;
+            };
+            let target = source.progress();
+            if target != progress_bar.value {
+                progress_bar.value = target;
+            }
+        }
+    }
+
+    fn spawn_layers(
+        mut commands: Commands,
+        query: Query<(Entity, &ProgressBar, &ProgressBarLayers, &Handle<Image>, &Sprite), Added<ProgressBarLayers>>,
+    ) {
+        for (entity, progress_bar, layers, texture, sprite) in query.iter() {
+            let mut bar = commands.entity(entity);
+            if layers.trail.is_some() || layers.heal.is_some() {
+                bar.insert(ProgressBarDisplay::new(progress_bar.value));
+            }
+
+            // Layers are children drawn just behind the bar.
+            let layer_colors = [
+                (ProgressBarLayer::Background, layers.background, -0.2),
+                (ProgressBarLayer::Trail, layers.trail.or(layers.heal), -0.1),
+            ];
+            for (layer, color, z) in layer_colors {
+                let Some(color) = color else {
+                    continue;
+                };
+                bar.with_children(|parent| {
+                    parent.spawn((
+                        layer,
+                        SpriteBundle {
+                            texture: texture.clone(),
+                            sprite: Sprite {
+                                anchor: sprite.anchor,
+                                color,
+                                ..Default::default()
+                            },
+                            transform: Transform::from_xyz(0.0, 0.0, z),
+                            ..Default::default()
+                        },
+                    ));
+                });
+            }
+
+            // The frame goes in front so the fill never covers it.
+            if let Some(frame) = &layers.frame {
+                bar.with_children(|parent| {
+                    for piece in 0..9 {
+                        parent.spawn((
+                            ProgressBarLayer::Frame(piece),
+                            SpriteBundle {
+                                texture: frame.texture.clone(),
+                                transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                                ..Default::default()
+                            },
+                        ));
+                    }
+                });
+            }
+        }
+    }
+
+    fn spawn_nodes(
+        mut commands: Commands,
+        mut query: Query<
+            (
+                Entity,
+                &ProgressBar,
+                &ProgressBarNode,
+                &ProgressBarLayers,
+                &mut Style,
+                &mut BackgroundColor,
+            ),
+            Added<ProgressBarNode>,
+        >,
+    ) {
+        for (entity, progress_bar, node, layers, mut style, mut background) in query.iter_mut() {
+            // The bar keeps its size in flex rows and its parts are placed inside it.
+            style.width = Val::Px(node.size.x);
+            style.height = Val::Px(node.size.y);
+            style.flex_shrink = 0.0;
+            if let Some(color) = layers.background {
+                *background = color.into();
+            }
+
+            let mut bar = commands.entity(entity);
+            if layers.trail.is_some() || layers.heal.is_some() {
+                bar.insert(ProgressBarDisplay::new(progress_bar.value));
+            }
+
+            let part_style = Style {
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            };
+            bar.with_children(|parent| {
+                if let Some(color) = layers.trail.or(layers.heal) {
+                    parent.spawn((
+                        ProgressBarNodePart::Trail,
+                        NodeBundle {
+                            style: part_style.clone(),
+                            background_color: color.into(),
+                            ..Default::default()
+                        },
+                    ));
+                }
+                parent
+                    .spawn((
+                        ProgressBarNodePart::Fill,
+                        NodeBundle {
+                            style: Style {
+                                overflow: Overflow::clip(),
+                                ..part_style.clone()
+                            },
+                            ..Default::default()
+                        },
+                    ))
+                    .with_children(|fill| {
+                        fill.spawn((
+                            ProgressBarNodePart::Image,
+                            ImageBundle {
+                                image: UiImage::new(node.texture.clone()),
+                                style: Style {
+                                    width: Val::Px(node.size.x),
+                                    height: Val::Px(node.size.y),
+                                    ..part_style.clone()
+                                },
+                                ..Default::default()
+                            },
+                        ));
+                    });
+            });
+        }
+    }
+
+    // Dividers are rebuilt whenever the number of segments or the direction
+    // changes, whether or not the bar has layers or a style.
+    fn spawn_dividers(
+        mut commands: Commands,
+        bars: DividedBarQuery,
+        dividers: Query<(), With<ProgressBarDivider>>,
+    ) {
+        for (entity, progress_bar, style, node, built, children) in bars.iter() {
+            let wanted = ProgressBarDividers {
+                segments: progress_bar.segments().unwrap_or(1),
+                direction: progress_bar.direction,
+            };
+            if built == Some(&wanted) {
+                continue;
+            }
+            for child in children.into_iter().flatten() {
+                if dividers.contains(*child) {
+                    commands.entity(*child).despawn_recursive();
+                }
+            }
+
+            let default_style = ProgressBarStyle::default();
+            let style = style.unwrap_or(&default_style);
+            let mut bar = commands.entity(entity);
+            bar.with_children(|parent| {
+                for segment in 1..wanted.segments {
+                    // Only center-out bars have a divider on each side.
+                    for mirrored in [false, true] {
+                        if mirrored && wanted.direction != FillDirection::CenterOut {
+                            continue;
+                        }
+                        if let Some(node) = node {
+                            // UI dividers don't move, so they're placed here.
+                            let fraction = segment as f32 / wanted.segments as f32;
+                            let bar_rect = Rect::from_corners(Vec2::ZERO, node.size);
+                            let (center, size) =
+                                divider_rect(wanted.direction, bar_rect, fraction, style.divider_width, mirrored);
+                            // Divider rects go up while UI rows go down.
+                            parent.spawn((
+                                ProgressBarDivider,
+                                NodeBundle {
+                                    style: Style {
+                                        position_type: PositionType::Absolute,
+                                        left: Val::Px(center.x - size.x / 2.0),
+                                        top: Val::Px(node.size.y - center.y - size.y / 2.0),
+                                        width: Val::Px(size.x),
+                                        height: Val::Px(size.y),
+                                        ..Default::default()
+                                    },
+                                    background_color: style.divider.into(),
+                                    ..Default::default()
+                                },
+                            ));
+                        } else {
+                            // Sprite dividers go over the fill so the segments
+                            // stay apart, and are placed once the image loads.
+                            parent.spawn((
+                                ProgressBarDivider,
+                                ProgressBarLayer::Divider(segment, mirrored),
+                                SpriteBundle {
+                                    sprite: Sprite {
+                                        color: style.divider,
+                                        custom_size: Some(Vec2::ZERO),
+                                        ..Default::default()
+                                    },
+                                    transform: Transform::from_xyz(0.0, 0.0, 0.05),
+                                    ..Default::default()
+                                },
+                            ));
+                        }
+                    }
+                }
+            });
+            bar.insert(wanted);
+        }
+    }
+
+    fn animate_layers(
+        mut query: Query<(&ProgressBar, &ProgressBarLayers, &mut ProgressBarDisplay)>,
+        time: Res<Time>,
+    ) {
+        for (progress_bar, layers, mut display) in query.iter_mut() {
+            let value = progress_bar.value;
+            let shown = if display.healing { display.trail } else { display.front };
+            if value < shown || (value > shown && layers.heal.is_none()) {
+                // The bar snaps and the trail is left behind to catch up.
+                let behind = f32::max(display.trail, display.front);
+                display.front = value;
+                display.trail = f32::max(behind, value);
+                display.from = display.trail;
+                display.elapsed = 0.0;
+                display.healing = false;
+            } else if value > shown {
+                // The heal layer snaps and the bar fills up to meet it.
+                display.trail = value;
+                display.from = display.front;
+                display.elapsed = 0.0;
+                display.healing = true;
+            }
+
+            display.elapsed += time.delta_seconds();
+            let t = f32::clamp((display.elapsed - layers.delay) / layers.duration.max(f32::EPSILON), 0.0, 1.0);
+            // Ease out so it slows down as it arrives.
+            let eased = 1.0 - (1.0 - t).powi(3);
+            let moved = display.from + (value - display.from) * eased;
+            if display.healing {
+                display.front = moved;
+            } else {
+                display.trail = moved;
+            }
+        }
+    }
+
+    fn notify(
+        query: Query<(Entity, &ProgressBar), Changed<ProgressBar>>,
+        mut removed: RemovedComponents<ProgressBar>,
+        mut last_values: Local<HashMap<Entity, f32>>,
+        mut changed: EventWriter<ProgressBarChanged>,
+        mut emptied: EventWriter<ProgressBarEmptied>,
+        mut filled: EventWriter<ProgressBarFilled>,
+    ) {
+        for entity in removed.read() {
+            last_values.remove(&entity);
+        }
+        for (entity, progress_bar) in query.iter() {
+            let new = progress_bar.value;
+            // New bars just start being tracked.
+            let Some(old) = last_values.insert(entity, new) else {
+                continue;
+            };
+            // NaN never equals itself, so check for it too or a stuck NaN
+            // would send a change every time the bar is touched.
+            if old == new || (old.is_nan() && new.is_nan()) {
+                continue;
+            }
+            changed.send(ProgressBarChanged { entity, old, new });
+            let (old, new) = (progress_bar.fraction(old), progress_bar.fraction(new));
+            if old > 0.0 && new <= 0.0 {
+                emptied.send(ProgressBarEmptied { entity });
+            }
+            if old < 1.0 && new >= 1.0 {
+                filled.send(ProgressBarFilled { entity });
+            }
+        }
+    }
+
+    // Targets are read before this frame's transforms are propagated, so
+    // bars trail a frame behind. That's not noticeable at the speeds here.
+    fn follow(
+        mut commands: Commands,
+        mut query: Query<(Entity, &ProgressBar, &mut ProgressBarFollow, &mut Transform, &mut Visibility)>,
+        targets: Query<&GlobalTransform>,
+        time: Res<Time>,
+    ) {
+        for (entity, progress_bar, mut follow, mut transform, mut visibility) in query.iter_mut() {
+            let Ok(target) = targets.get(follow.target) else {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            };
+            transform.translation = target.translation() + follow.offset;
+
+            let Some(hide_after) = follow.hide_when_full else {
+                continue;
+            };
+            if progress_bar.percent() >= 1.0 {
+                follow.full_for += time.delta_seconds();
+            } else {
+                follow.full_for = 0.0;
+            }
+            let hidden = follow.full_for >= hide_after;
+            let wanted = if hidden { Visibility::Hidden } else { Visibility::Inherited };
+            if *visibility != wanted {
+                *visibility = wanted;
+            }
+        }
+    }
+
     fn update(
-        mut query: Query<(&ProgressBar, &Handle<Image>, &mut Sprite)>,
+        mut query: Query<(&ProgressBar, Option<&ProgressBarDisplay>, &Handle<Image>, &mut Sprite), Without<ProgressBarLayer>>,
         assets: Res<Assets<Image>>,
     ) {
-        for (progress_bar, image_hanadle, mut sprite) in query.iter_mut() {
+        for (progress_bar, display, image_hanadle, mut sprite) in query.iter_mut() {
             // Fixed an issue here where panic would happen immediately on fail.
             // Instead, only proceed if Some(image) exists.
             //
@@ -75,16 +828,398 @@ impl ProgressBarPlugin {
             //    .expect(format!("Image {:?} not found", image_hanadle).as_str());
 
             if let Some(image) = assets.get(image_hanadle) {
-                let progress_percent =
-                    f32::clamp(progress_bar.value / progress_bar.max_value, 0.0, 1.0);
-                let width = image.width() as f32;
+                let value = display.map_or(progress_bar.value, |display| display.front);
+                crop(&mut sprite, image, progress_bar.direction, progress_bar.shown_percent(value));
+            }
+        }
+    }
 
-                sprite.rect = Some(Rect {
-                    min: Vec2::ZERO,
-                    max: Vec2::new(width * progress_percent, image.height() as f32),
-                });
-                sprite.custom_size = Some(Vec2::new(width * progress_percent, image.height() as f32));
+    fn update_colors(
+        mut query: Query<(&ProgressBar, &ProgressBarStyle, &mut Sprite), Without<ProgressBarLayer>>,
+        time: Res<Time>,
+    ) {
+        for (progress_bar, style, mut sprite) in query.iter_mut() {
+            let percent = progress_bar.percent();
+            if let Some(color) = style.color(percent, time.elapsed_seconds()) {
+                sprite.color = color;
+            }
+        }
+    }
+
+    fn update_nodes(
+        mut parts: Query<(&ProgressBarNodePart, &Parent, &mut Style, &mut BackgroundColor)>,
+        fills: Query<&Parent, With<ProgressBarNodePart>>,
+        bars: Query<(
+            &ProgressBar,
+            &ProgressBarNode,
+            &ProgressBarLayers,
+            &ProgressBarStyle,
+            Option<&ProgressBarDisplay>,
+        )>,
+        time: Res<Time>,
+    ) {
+        for (part, parent, mut style, mut background) in parts.iter_mut() {
+            // Images sit inside the fill, so their bar is one step further up.
+            let bar = match part {
+                ProgressBarNodePart::Image => match fills.get(parent.get()) {
+                    Ok(fill_parent) => fill_parent.get(),
+                    Err(_) => continue,
+                },
+                _ => parent.get(),
+            };
+            let Ok((progress_bar, node, layers, bar_style, display)) = bars.get(bar) else {
+                continue;
+            };
+
+            let front = display.map_or(progress_bar.value, |display| display.front);
+            let percent = match (part, display) {
+                (ProgressBarNodePart::Trail, Some(display)) => {
+                    let color = if display.healing { layers.heal } else { layers.trail };
+                    if let Some(color) = color {
+                        *background = color.into();
+                    }
+                    // Hidden by the bar when there's no trail color for this direction.
+                    color.map_or(0.0, |_| progress_bar.shown_percent(display.trail))
+                }
+                (ProgressBarNodePart::Trail, None) => 0.0,
+                _ => progress_bar.shown_percent(front),
+            };
+            let rect = progress_bar
+                .direction
+                .visible_rect(node.size, f32::clamp(percent, 0.0, 1.0));
+
+            if *part == ProgressBarNodePart::Image {
+                // Shift the image back so the clipped fill shows the right part.
+                style.left = Val::Px(-rect.min.x);
+                style.top = Val::Px(-rect.min.y);
+                let percent = progress_bar.percent();
+                let color = bar_style.color(percent, time.elapsed_seconds());
+                *background = color.unwrap_or(Color::WHITE).into();
+            } else {
+                style.left = Val::Px(rect.min.x);
+                style.top = Val::Px(rect.min.y);
+                style.width = Val::Px(rect.width());
+                style.height = Val::Px(rect.height());
+            }
+        }
+    }
+
+    fn update_layers(
+        mut query: Query<(&ProgressBarLayer, &Parent, &Handle<Image>, &mut Sprite, &mut Transform), With<ProgressBarLayer>>,
+        bars: Query<(
+            &ProgressBar,
+            Option<&ProgressBarLayers>,
+            Option<&ProgressBarStyle>,
+            Option<&ProgressBarDisplay>,
+            &Handle<Image>,
+        )>,
+        assets: Res<Assets<Image>>,
+    ) {
+        for (layer, parent, image_handle, mut sprite, mut transform) in query.iter_mut() {
+            let Ok((progress_bar, layers, style, display, bar_image)) = bars.get(parent.get()) else {
+                continue;
+            };
+            let Some(bar_image) = assets.get(bar_image) else {
+                continue;
+            };
+            let bar_rect = progress_bar.direction.local_rect(bar_image.size().as_vec2());
+            let direction = progress_bar.direction;
+
+            // Dividers are plain colored sprites.
+            if let ProgressBarLayer::Divider(segment, mirrored) = *layer {
+                let Some(segments) = progress_bar.segments() else {
+                    continue;
+                };
+                let fraction = segment as f32 / segments as f32;
+                let width = style.map_or(1.0, |style| style.divider_width);
+                let (center, size) = divider_rect(direction, bar_rect, fraction, width, mirrored);
+                sprite.anchor = Anchor::Center;
+                sprite.custom_size = Some(size);
+                transform.translation.x = center.x;
+                transform.translation.y = center.y;
+                continue;
+            }
+
+            let (Some(layers), Some(image)) = (layers, assets.get(image_handle)) else {
+                continue;
+            };
+            match (layer, display) {
+                (ProgressBarLayer::Background, _) => crop(&mut sprite, image, direction, 1.0),
+                (ProgressBarLayer::Trail, Some(display)) => {
+                    let color = if display.healing { layers.heal } else { layers.trail };
+                    // Hidden by the bar when there's no trail color for this direction.
+                    let percent = if color.is_some() { progress_bar.shown_percent(display.trail) } else { 0.0 };
+                    if let Some(color) = color {
+                        sprite.color = color;
+                    }
+                    crop(&mut sprite, image, direction, percent);
+                }
+                (ProgressBarLayer::Trail, None) => crop(&mut sprite, image, direction, 0.0),
+                (ProgressBarLayer::Divider(..), _) => {}
+                (ProgressBarLayer::Frame(piece), _) => {
+                    let Some(frame) = &layers.frame else {
+                        continue;
+                    };
+                    let outer = Rect::from_corners(
+                        bar_rect.min - Vec2::splat(frame.padding + frame.border),
+                        bar_rect.max + Vec2::splat(frame.padding + frame.border),
+                    );
+                    let (source, target) = nine_slice_piece(*piece, image.size().as_vec2(), outer, frame.border);
+                    sprite.anchor = Anchor::Center;
+                    sprite.rect = Some(source);
+                    sprite.custom_size = Some(target.size());
+                    transform.translation.x = target.center().x;
+                    transform.translation.y = target.center().y;
+                }
             }
         }
     }
 }
+
+// Show `percent` of the image, filling in `direction`.
+fn crop(sprite: &mut Sprite, image: &Image, direction: FillDirection, percent: f32) {
+    let progress_percent = f32::clamp(percent, 0.0, 1.0);
+    let rect = direction.visible_rect(image.size().as_vec2(), progress_percent);
+
+    sprite.anchor = direction.anchor();
+    sprite.rect = Some(rect);
+    sprite.custom_size = Some(rect.size());
+}
+
+// Center and size of the divider `fraction` of the way along a bar.
+fn divider_rect(direction: FillDirection, bar: Rect, fraction: f32, width: f32, mirrored: bool) -> (Vec2, Vec2) {
+    let (w, h) = (bar.width(), bar.height());
+    let center = bar.center();
+    match (direction, mirrored) {
+        (FillDirection::LeftToRight, false) => (Vec2::new(bar.min.x + w * fraction, center.y), Vec2::new(width, h)),
+        (FillDirection::RightToLeft, false) => (Vec2::new(bar.max.x - w * fraction, center.y), Vec2::new(width, h)),
+        (FillDirection::BottomToTop, false) => (Vec2::new(center.x, bar.min.y + h * fraction), Vec2::new(w, width)),
+        (FillDirection::TopToBottom, false) => (Vec2::new(center.x, bar.max.y - h * fraction), Vec2::new(w, width)),
+        (FillDirection::CenterOut, mirrored) => {
+            let offset = w * fraction / 2.0;
+            let x = if mirrored { center.x - offset } else { center.x + offset };
+            (Vec2::new(x, center.y), Vec2::new(width, h))
+        }
+        // Only center-out bars have mirrored dividers.
+        (_, true) => (center, Vec2::ZERO),
+    }
+}
+
+// The image rect and local rect of one of the nine frame pieces. Pieces are
+// numbered left to right, top to bottom, so 4 is the stretched middle.
+fn nine_slice_piece(piece: usize, image_size: Vec2, outer: Rect, border: f32) -> (Rect, Rect) {
+    let (column, row) = (piece % 3, piece / 3);
+    let border = border.min(image_size.x / 2.0).min(image_size.y / 2.0);
+    let image_xs = [0.0, border, image_size.x - border, image_size.x];
+    // Image rows go down while local y goes up.
+    let image_ys = [0.0, border, image_size.y - border, image_size.y];
+    let local_xs = [outer.min.x, outer.min.x + border, outer.max.x - border, outer.max.x];
+    let local_ys = [outer.max.y, outer.max.y - border, outer.min.y + border, outer.min.y];
+
+    let source = Rect::new(image_xs[column], image_ys[row], image_xs[column + 1], image_ys[row + 1]);
+    let target = Rect::new(local_xs[column], local_ys[row + 1], local_xs[column + 1], local_ys[row]);
+    (source, target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::{
+        asset::AssetId,
+        ecs::event::Events,
+        render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    };
+
+    fn test_image(width: u32, height: u32) -> Image {
+        Image::new_fill(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[255, 255, 255, 255],
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    // A headless app with just enough to run the plugin's systems.
+    fn test_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<Assets<Image>>()
+            .add_plugins(ProgressBarPlugin);
+        app
+    }
+
+    fn spawn_bar(app: &mut App, value: f32, max_value: f32, direction: FillDirection) -> Entity {
+        let texture = app.world.resource_mut::<Assets<Image>>().add(test_image(100, 10));
+        let mut bundle = ProgressBarBundle::new(max_value, texture);
+        bundle.progresss_bar.value = value;
+        bundle.progresss_bar.direction = direction;
+        app.world.spawn(bundle).id()
+    }
+
+    fn sprite(app: &App, entity: Entity) -> &Sprite {
+        app.world.get::<Sprite>(entity).unwrap()
+    }
+
+    #[test]
+    fn crops_to_value() {
+        let mut app = test_app();
+        let bar = spawn_bar(&mut app, 25.0, 100.0, FillDirection::LeftToRight);
+        app.update();
+
+        assert_eq!(sprite(&app, bar).rect, Some(Rect::new(0.0, 0.0, 25.0, 10.0)));
+        assert_eq!(sprite(&app, bar).custom_size, Some(Vec2::new(25.0, 10.0)));
+    }
+
+    #[test]
+    fn crops_from_the_fill_edge() {
+        let mut app = test_app();
+        let right = spawn_bar(&mut app, 25.0, 100.0, FillDirection::RightToLeft);
+        let center = spawn_bar(&mut app, 50.0, 100.0, FillDirection::CenterOut);
+        app.update();
+
+        assert_eq!(sprite(&app, right).rect, Some(Rect::new(75.0, 0.0, 100.0, 10.0)));
+        assert_eq!(sprite(&app, right).anchor.as_vec(), Anchor::CenterRight.as_vec());
+        assert_eq!(sprite(&app, center).rect, Some(Rect::new(25.0, 0.0, 75.0, 10.0)));
+        assert_eq!(sprite(&app, center).anchor.as_vec(), Anchor::Center.as_vec());
+    }
+
+    #[test]
+    fn bad_max_shows_empty() {
+        let mut app = test_app();
+        let zero = spawn_bar(&mut app, 10.0, 0.0, FillDirection::LeftToRight);
+        let negative = spawn_bar(&mut app, 10.0, -5.0, FillDirection::LeftToRight);
+        let nan = spawn_bar(&mut app, f32::NAN, 100.0, FillDirection::LeftToRight);
+        app.update();
+
+        for bar in [zero, negative, nan] {
+            assert_eq!(sprite(&app, bar).rect, Some(Rect::new(0.0, 0.0, 0.0, 10.0)));
+            assert_eq!(sprite(&app, bar).custom_size, Some(Vec2::new(0.0, 10.0)));
+        }
+    }
+
+    #[test]
+    fn overfill_shows_full() {
+        let mut app = test_app();
+        let bar = spawn_bar(&mut app, 150.0, 100.0, FillDirection::LeftToRight);
+        app.update();
+
+        assert_eq!(sprite(&app, bar).rect, Some(Rect::new(0.0, 0.0, 100.0, 10.0)));
+        assert_eq!(sprite(&app, bar).custom_size, Some(Vec2::new(100.0, 10.0)));
+    }
+
+    #[test]
+    fn segments_round_up() {
+        let mut app = test_app();
+        let bar = spawn_bar(&mut app, 41.0, 100.0, FillDirection::LeftToRight);
+        app.world.get_mut::<ProgressBar>(bar).unwrap().step = 10.0;
+        app.update();
+
+        assert_eq!(sprite(&app, bar).custom_size, Some(Vec2::new(50.0, 10.0)));
+    }
+
+    fn dividers(app: &mut App, bar: Entity) -> Vec<Vec2> {
+        app.world
+            .query_filtered::<(&Parent, &Sprite), With<ProgressBarDivider>>()
+            .iter(&app.world)
+            .filter(|(parent, _)| parent.get() == bar)
+            .filter_map(|(_, sprite)| sprite.custom_size)
+            .collect()
+    }
+
+    #[test]
+    fn dividers_follow_the_step() {
+        let mut app = test_app();
+        let bar = spawn_bar(&mut app, 41.0, 100.0, FillDirection::LeftToRight);
+        app.update();
+        assert!(dividers(&mut app, bar).is_empty());
+
+        app.world.get_mut::<ProgressBar>(bar).unwrap().step = 10.0;
+        app.update();
+        assert_eq!(dividers(&mut app, bar).len(), 9);
+        // Center-out bars have one on each side of the middle.
+        app.world.get_mut::<ProgressBar>(bar).unwrap().direction = FillDirection::CenterOut;
+        app.update();
+        assert_eq!(dividers(&mut app, bar).len(), 18);
+        app.world.get_mut::<ProgressBar>(bar).unwrap().step = 0.0;
+        app.update();
+        assert!(dividers(&mut app, bar).is_empty());
+
+        // Bars without layers or a style are split up too.
+        let texture = app.world.resource_mut::<Assets<Image>>().add(test_image(100, 10));
+        let plain = app
+            .world
+            .spawn((
+                ProgressBar {
+                    value: 50.0,
+                    max_value: 100.0,
+                    step: 25.0,
+                    ..Default::default()
+                },
+                SpriteBundle {
+                    texture,
+                    ..Default::default()
+                },
+            ))
+            .id();
+        app.update();
+        app.update();
+        assert_eq!(dividers(&mut app, plain), vec![Vec2::new(1.0, 10.0); 3]);
+    }
+
+    #[test]
+    fn waits_for_image() {
+        let mut app = test_app();
+        let id = AssetId::<Image>::Uuid {
+            uuid: bevy::utils::Uuid::from_u128(0x5eed),
+        };
+        let bar = app
+            .world
+            .spawn(ProgressBarBundle::new(100.0, Handle::Weak(id)))
+            .id();
+        app.world.get_mut::<ProgressBar>(bar).unwrap().value = 50.0;
+        app.update();
+        assert_eq!(sprite(&app, bar).rect, None);
+
+        app.world.resource_mut::<Assets<Image>>().insert(id, test_image(100, 10));
+        app.update();
+        assert_eq!(sprite(&app, bar).rect, Some(Rect::new(0.0, 0.0, 50.0, 10.0)));
+    }
+
+    #[test]
+    fn sends_emptied_and_filled() {
+        let mut app = test_app();
+        let bar = spawn_bar(&mut app, 100.0, 100.0, FillDirection::LeftToRight);
+        app.update();
+
+        app.world.get_mut::<ProgressBar>(bar).unwrap().value = -20.0;
+        app.update();
+        let emptied = app.world.resource::<Events<ProgressBarEmptied>>();
+        assert_eq!(emptied.len(), 1);
+
+        app.world.get_mut::<ProgressBar>(bar).unwrap().value = 120.0;
+        app.update();
+        let filled = app.world.resource::<Events<ProgressBarFilled>>();
+        assert_eq!(filled.len(), 1);
+    }
+
+    #[test]
+    fn follower_stays_hidden_until_the_value_drops() {
+        let mut app = test_app();
+        let target = app.world.spawn(GlobalTransform::from_xyz(10.0, 20.0, 0.0)).id();
+        let bar = spawn_bar(&mut app, 100.0, 100.0, FillDirection::LeftToRight);
+        app.world.entity_mut(bar).insert(
+            ProgressBarFollow::new(target, Vec3::new(0.0, 8.0, 0.0)).with_hide_when_full(1.5)
+        );
+        app.update();
+        assert_eq!(app.world.get::<Visibility>(bar), Some(&Visibility::Hidden));
+        assert_eq!(app.world.get::<Transform>(bar).unwrap().translation, Vec3::new(10.0, 28.0, 0.0));
+
+        app.world.get_mut::<ProgressBar>(bar).unwrap().value = 90.0;
+        app.update();
+        assert_eq!(app.world.get::<Visibility>(bar), Some(&Visibility::Inherited));
+    }
+}
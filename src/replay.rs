@@ -0,0 +1,139 @@
+// Recorded matches, for reproducing bugs and running scripted tests.
+//
+// A replay is the match seed and settings plus both players' inputs for
+// every frame. Matches are stepped by a fixed NET_DT while recording or
+// playing back, so the same seed, settings and inputs play out the same way.
+
+use std::io;
+use std::path::Path;
+
+use crate::difficulty::Difficulty;
+use crate::game::GameMode;
+use crate::net::NetInput;
+
+const REPLAY_MAGIC: &[u8; 8] = b"SNOWRPL2";
+// The magic, the seed and the settings.
+const HEADER_LEN: usize = 19;
+
+// The choices made before the match that change how it plays out.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReplaySettings {
+    pub mode: GameMode,
+    pub difficulty: Difficulty,
+    pub adaptive: bool
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub settings: ReplaySettings,
+    // Inputs for player slots 0 and 1.
+    pub frames: Vec<[NetInput; 2]>
+}
+
+fn difficulty_from_byte(byte: u8) -> Option<Difficulty> {
+    match byte {
+        0 => Some(Difficulty::Easy),
+        1 => Some(Difficulty::Normal),
+        2 => Some(Difficulty::Hard),
+        _ => None
+    }
+}
+
+fn difficulty_to_byte(difficulty: Difficulty) -> u8 {
+    match difficulty {
+        Difficulty::Easy => 0,
+        Difficulty::Normal => 1,
+        Difficulty::Hard => 2
+    }
+}
+
+impl Replay {
+    pub fn new(seed: u64, settings: ReplaySettings) -> Self {
+        Self {
+            seed,
+            settings,
+            frames: Vec::new()
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.frames.len() * 2);
+        bytes.extend_from_slice(REPLAY_MAGIC);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.push(self.settings.mode.to_byte());
+        bytes.push(difficulty_to_byte(self.settings.difficulty));
+        bytes.push(u8::from(self.settings.adaptive));
+        for [one, two] in &self.frames {
+            bytes.push(one.0);
+            bytes.push(two.0);
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let rest = bytes.strip_prefix(REPLAY_MAGIC)?;
+        let header_len = HEADER_LEN - REPLAY_MAGIC.len();
+        if rest.len() < header_len || (rest.len() - header_len) % 2 != 0 {
+            return None;
+        }
+        let (header, frames) = rest.split_at(header_len);
+        let (seed, settings) = header.split_at(8);
+        Some(Self {
+            seed: u64::from_le_bytes(seed.try_into().ok()?),
+            settings: ReplaySettings {
+                mode: GameMode::from_byte(settings[0])?,
+                difficulty: difficulty_from_byte(settings[1])?,
+                adaptive: match settings[2] {
+                    0 => false,
+                    1 => true,
+                    _ => return None
+                }
+            },
+            frames: frames.chunks_exact(2)
+                .map(|frame| [NetInput(frame[0]), NetInput(frame[1])])
+                .collect()
+        })
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Self::decode(&bytes).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a replay file"))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        std::fs::write(path, self.encode())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let replay = Replay {
+            seed: 0xdead_beef_1234,
+            settings: ReplaySettings {
+                mode: GameMode::Endless,
+                difficulty: Difficulty::Hard,
+                adaptive: true
+            },
+            frames: vec![
+                [NetInput::new(true, false, false), NetInput::default()],
+                [NetInput::new(false, true, true), NetInput::new(true, false, true)]
+            ]
+        };
+        let bytes = replay.encode();
+        assert_eq!(Replay::decode(&bytes), Some(replay));
+
+        assert_eq!(Replay::decode(b"SNOWRPL2"), None);
+        // Replays from before the settings were saved aren't played back.
+        assert_eq!(Replay::decode(b"SNOWRPL1\x01\0\0\0\0\0\0\0"), None);
+        assert_eq!(Replay::decode(&bytes[..bytes.len() - 1]), None);
+        let mut bad_mode = bytes.clone();
+        bad_mode[16] = 99;
+        assert_eq!(Replay::decode(&bad_mode), None);
+        assert_eq!(Replay::decode(b"not a replay at all"), None);
+    }
+}
@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+
+use crate::init::Renderer;
+
+// Player settings, saved next to the game as simple `key = value` lines.
+// The web build has nowhere to save them, so it always uses the defaults.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct Settings {
+    // How hard the camera shakes, from 0 (off) to 1.
+    pub screen_shake: f32,
+    // Briefly freeze the game when the bear gets hit.
+    pub hit_stop: bool,
+    pub fullscreen: bool,
+    // Scanlines over the game, see display.rs.
+    pub crt: bool,
+    // Only read at startup, see init.rs.
+    pub renderer: Renderer
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            screen_shake: 1.0,
+            hit_stop: true,
+            fullscreen: false,
+            crt: false,
+            renderer: Renderer::Auto
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+const SETTINGS_PATH: &str = "settings.cfg";
+
+impl Settings {
+    pub fn parse(text: &str) -> Self {
+        let mut settings = Settings::default();
+        for line in text.lines() {
+            // Unknown keys and bad values are skipped so old files still load.
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "screen_shake" => if let Ok(shake) = value.parse::<f32>() {
+                    settings.screen_shake = shake.clamp(0.0, 1.0);
+                },
+                "hit_stop" => if let Ok(hit_stop) = value.parse() {
+                    settings.hit_stop = hit_stop;
+                },
+                "fullscreen" => if let Ok(fullscreen) = value.parse() {
+                    settings.fullscreen = fullscreen;
+                },
+                "crt" => if let Ok(crt) = value.parse() {
+                    settings.crt = crt;
+                },
+                "renderer" => if let Some(renderer) = Renderer::from_name(value) {
+                    settings.renderer = renderer;
+                },
+                _ => {}
+            }
+        }
+        settings
+    }
+
+    pub fn to_text(&self) -> String {
+        format!(
+            "screen_shake = {}\nhit_stop = {}\nfullscreen = {}\ncrt = {}\nrenderer = {}\n",
+            self.screen_shake,
+            self.hit_stop,
+            self.fullscreen,
+            self.crt,
+            self.renderer.name()
+        )
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> Self {
+        std::fs::read_to_string(SETTINGS_PATH)
+            .map(|text| Settings::parse(&text))
+            .unwrap_or_default()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load() -> Self {
+        Settings::default()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self) {
+        if let Err(err) = std::fs::write(SETTINGS_PATH, self.to_text()) {
+            warn!("Couldn't save settings to {SETTINGS_PATH}: {err}");
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&self) {}
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Settings::load());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_skips_bad_lines() {
+        let settings = Settings {
+            screen_shake: 0.5,
+            hit_stop: false,
+            fullscreen: true,
+            crt: true,
+            renderer: Renderer::Gl
+        };
+        assert_eq!(Settings::parse(&settings.to_text()), settings);
+
+        let parsed = Settings::parse("screen_shake = lots\nhit_stop=false\nvolume = 3\nnonsense");
+        assert_eq!(parsed, Settings { hit_stop: false, ..Settings::default() });
+        assert_eq!(Settings::parse("screen_shake = 7").screen_shake, 1.0);
+    }
+}
@@ -0,0 +1,139 @@
+// Statistics kept during a match and shown on the results screens.
+
+use bevy::prelude::*;
+
+use crate::endless::COMBO_SECS;
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct MatchStats {
+    pub seconds: f32,
+    // Snow tiles launched by the players.
+    pub launched: u32,
+    // Snow tiles that hit something, even if a shield soaked it all up.
+    pub hits: u32,
+    pub damage_dealt: f32,
+    pub damage_taken: f32,
+    // Enemy projectiles that reached the ground without hurting anyone.
+    pub dodged: u32,
+    pub longest_combo: u32,
+    combo: u32,
+    since_hit: f32
+}
+
+impl Default for MatchStats {
+    fn default() -> Self {
+        Self {
+            seconds: 0.0,
+            launched: 0,
+            hits: 0,
+            damage_dealt: 0.0,
+            damage_taken: 0.0,
+            dodged: 0,
+            longest_combo: 0,
+            combo: 0,
+            since_hit: f32::INFINITY
+        }
+    }
+}
+
+impl MatchStats {
+    pub fn tick(&mut self, delta_secs: f32) {
+        self.seconds += delta_secs;
+        self.since_hit += delta_secs;
+    }
+
+    pub fn launch(&mut self) {
+        self.launched += 1;
+    }
+
+    // Combos work like in endless mode: quick hits without getting hurt.
+    pub fn hit(&mut self) {
+        self.hits += 1;
+        self.combo = if self.since_hit <= COMBO_SECS { self.combo + 1 } else { 1 };
+        self.longest_combo = self.longest_combo.max(self.combo);
+        self.since_hit = 0.0;
+    }
+
+    pub fn deal(&mut self, damage: f32) {
+        self.damage_dealt += damage;
+    }
+
+    pub fn hurt(&mut self, damage: f32) {
+        self.damage_taken += damage;
+        self.combo = 0;
+        self.since_hit = f32::INFINITY;
+    }
+
+    pub fn dodge(&mut self) {
+        self.dodged += 1;
+    }
+
+    // Hits per launch, or None before anything was launched.
+    pub fn accuracy(&self) -> Option<f32> {
+        if self.launched == 0 {
+            return None;
+        }
+        Some((self.hits as f32 / self.launched as f32).min(1.0))
+    }
+
+    // Two to a line, for the results screens.
+    pub fn summary(&self) -> String {
+        let seconds = self.seconds as u32;
+        let accuracy = match self.accuracy() {
+            Some(accuracy) => format!("{:.0}%", accuracy * 100.0),
+            None => "-".to_string()
+        };
+        format!(
+            "Time {}:{:02}   Longest combo x{}\n\
+            Snow launched {}   Hits {} ({accuracy})\n\
+            Damage dealt {:.0}   Damage taken {:.0}\n\
+            Projectiles dodged {}",
+            seconds / 60,
+            seconds % 60,
+            self.longest_combo,
+            self.launched,
+            self.hits,
+            self.damage_dealt,
+            self.damage_taken,
+            self.dodged
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_a_match() {
+        let mut stats = MatchStats::default();
+        assert_eq!(stats.accuracy(), None);
+        for _ in 0..4 {
+            stats.launch();
+        }
+        stats.hit();
+        stats.deal(3.0);
+        stats.tick(1.0);
+        stats.hit();
+        stats.deal(4.5);
+        stats.hurt(10.0);
+        stats.hit();
+        stats.deal(2.0);
+        // A hit a shield soaked up is still a hit.
+        stats.hit();
+        stats.deal(0.0);
+        stats.dodge();
+        stats.tick(64.0);
+
+        assert_eq!(stats.accuracy(), Some(1.0));
+        assert_eq!(stats.longest_combo, 2);
+        assert_eq!(stats.damage_dealt, 9.5);
+        assert_eq!(
+            stats.summary(),
+            "Time 1:05   Longest combo x2\n\
+            Snow launched 4   Hits 4 (100%)\n\
+            Damage dealt 10   Damage taken 10\n\
+            Projectiles dodged 1"
+        );
+    }
+}
@@ -0,0 +1,339 @@
+// Time attack mode. Just the bear, against the clock: a split is taken each
+// time its health passes another quarter, and the run is raced against the
+// personal best, whose player is shown as a ghost. Each difficulty, with and
+// without adaptive difficulty, keeps its own best.
+
+use bevy::prelude::*;
+
+use crate::difficulty::Difficulty;
+
+// Splits are taken as the bear's health drops past these fractions.
+pub const SPLIT_MARKS: [f32; 4] = [0.75, 0.5, 0.25, 0.0];
+// How often the player's position is saved for the ghost.
+const GHOST_DT: f32 = 1.0 / 20.0;
+
+#[cfg(not(target_arch = "wasm32"))]
+const PERSONAL_BEST_PATH: &str = "timeattack.cfg";
+
+// The settings a run was played on. Runs only race bests set on the same
+// ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PersonalBestKey {
+    pub difficulty: Difficulty,
+    pub adaptive: bool
+}
+
+impl PersonalBestKey {
+    // Like `normal` or `hard-adaptive`, for the file.
+    pub fn name(self) -> String {
+        let difficulty = self.difficulty.name().to_ascii_lowercase();
+        if self.adaptive { format!("{difficulty}-adaptive") } else { difficulty }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let (difficulty, adaptive) = match name.strip_suffix("-adaptive") {
+            Some(difficulty) => (difficulty, true),
+            None => (name, false)
+        };
+        Some(Self {
+            difficulty: Difficulty::from_name(difficulty)?,
+            adaptive
+        })
+    }
+}
+
+// The fastest run so far on one set of settings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PersonalBest {
+    // Seconds at each split mark.
+    pub splits: Vec<f32>,
+    // Player positions every GHOST_DT seconds.
+    pub ghost: Vec<Vec2>
+}
+
+impl PersonalBest {
+    // The whole run's time, if there is a best yet.
+    pub fn time(&self) -> Option<f32> {
+        if self.splits.len() == SPLIT_MARKS.len() {
+            self.splits.last().copied()
+        } else {
+            None
+        }
+    }
+
+    // Where the ghost is `seconds` into the run, or None once it's done.
+    pub fn ghost_at(&self, seconds: f32) -> Option<Vec2> {
+        let at = seconds.max(0.0) / GHOST_DT;
+        let i = at as usize;
+        let from = *self.ghost.get(i)?;
+        Some(match self.ghost.get(i + 1) {
+            Some(to) => from.lerp(*to, at.fract()),
+            None => from
+        })
+    }
+}
+
+// Every set of settings' best, saved next to the game as `key = value` lines
+// like `hard-adaptive.splits = ...`.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct PersonalBests {
+    bests: Vec<(PersonalBestKey, PersonalBest)>
+}
+
+impl PersonalBests {
+    // The best to race on these settings, empty if there isn't one yet.
+    pub fn get(&self, key: PersonalBestKey) -> PersonalBest {
+        self.bests.iter()
+            .find(|(other, _)| *other == key)
+            .map(|(_, best)| best.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set(&mut self, key: PersonalBestKey, best: PersonalBest) {
+        match self.bests.iter_mut().find(|(other, _)| *other == key) {
+            Some((_, old)) => *old = best,
+            None => self.bests.push((key, best))
+        }
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut bests = PersonalBests::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            // Lines without settings in the key, from before bests were
+            // kept per difficulty, are skipped too.
+            let Some((name, field)) = key.trim().split_once('.') else {
+                continue;
+            };
+            let Some(key) = PersonalBestKey::from_name(name) else {
+                continue;
+            };
+            let mut best = bests.get(key);
+            match field {
+                "splits" => best.splits = value.split_whitespace()
+                    .map_while(|split| split.parse().ok())
+                    .collect(),
+                "ghost" => best.ghost = value.split_whitespace()
+                    .map_while(|point| {
+                        let (x, y) = point.split_once(',')?;
+                        Some(Vec2::new(x.parse().ok()?, y.parse().ok()?))
+                    })
+                    .collect(),
+                _ => continue
+            }
+            bests.set(key, best);
+        }
+        // A best with missing splits can't be raced.
+        bests.bests.retain(|(_, best)| best.time().is_some());
+        bests
+    }
+
+    pub fn to_text(&self) -> String {
+        self.bests.iter()
+            .map(|(key, best)| {
+                let name = key.name();
+                let splits: Vec<String> = best.splits.iter().map(|split| split.to_string()).collect();
+                let ghost: Vec<String> = best.ghost.iter().map(|point| format!("{},{}", point.x, point.y)).collect();
+                format!("{name}.splits = {}\n{name}.ghost = {}\n", splits.join(" "), ghost.join(" "))
+            })
+            .collect()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load() -> Self {
+        std::fs::read_to_string(PERSONAL_BEST_PATH)
+            .map(|text| PersonalBests::parse(&text))
+            .unwrap_or_default()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn load() -> Self {
+        PersonalBests::default()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self) {
+        if let Err(err) = std::fs::write(PERSONAL_BEST_PATH, self.to_text()) {
+            warn!("Couldn't save the time attack bests to {PERSONAL_BEST_PATH}: {err}");
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&self) {}
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct TimeAttackRun {
+    pub seconds: f32,
+    pub splits: Vec<f32>,
+    pub ghost: Vec<Vec2>,
+    // The settings this run is on, and the best on them from before it
+    // started.
+    pub key: PersonalBestKey,
+    pub best: PersonalBest,
+    pub new_best: bool
+}
+
+impl TimeAttackRun {
+    pub fn new(key: PersonalBestKey, best: PersonalBest) -> Self {
+        Self {
+            seconds: 0.0,
+            splits: Vec::new(),
+            ghost: Vec::new(),
+            key,
+            best,
+            new_best: false
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.splits.len() == SPLIT_MARKS.len()
+    }
+
+    // The clock stops once the bear is down.
+    pub fn tick(&mut self, delta_secs: f32, player: Option<Vec2>) {
+        if self.finished() {
+            return;
+        }
+        self.seconds += delta_secs;
+        if let Some(player) = player {
+            while self.ghost.len() as f32 * GHOST_DT <= self.seconds {
+                self.ghost.push(player);
+            }
+        }
+    }
+
+    // Takes a split for every mark the bear's health has dropped past.
+    // Returns true if any were taken.
+    pub fn update_health(&mut self, fraction: f32) -> bool {
+        let taken = self.splits.len();
+        while !self.finished() && fraction <= SPLIT_MARKS[self.splits.len()] {
+            self.splits.push(self.seconds);
+        }
+        self.splits.len() > taken
+    }
+
+    // Ahead of the best at a split is negative.
+    pub fn split_delta(&self, split: usize) -> Option<f32> {
+        Some(self.splits.get(split)? - self.best.splits.get(split)?)
+    }
+
+    // The new personal best, if a finished run beat the old one.
+    pub fn finish(&mut self) -> Option<PersonalBest> {
+        let beaten = match self.best.time() {
+            Some(best) => self.seconds < best,
+            None => true
+        };
+        if !self.finished() || !beaten {
+            return None;
+        }
+        self.new_best = true;
+        Some(PersonalBest {
+            splits: self.splits.clone(),
+            ghost: self.ghost.clone()
+        })
+    }
+}
+
+// Minutes, seconds and hundredths.
+pub fn format_time(seconds: f32) -> String {
+    let hundredths = (seconds.max(0.0) * 100.0).round() as u32;
+    format!("{}:{:02}.{:02}", hundredths / 6000, hundredths / 100 % 60, hundredths % 100)
+}
+
+pub fn format_delta(delta: f32) -> String {
+    format!("{}{:.2}", if delta < 0.0 { "-" } else { "+" }, delta.abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NORMAL: PersonalBestKey = PersonalBestKey {
+        difficulty: Difficulty::Normal,
+        adaptive: false
+    };
+
+    // Knocks a quarter off the bear every `quarter_secs`.
+    fn run_against(best: PersonalBest, quarter_secs: f32) -> TimeAttackRun {
+        let mut run = TimeAttackRun::new(NORMAL, best);
+        for quarter in 1..=4 {
+            run.tick(quarter_secs, Some(Vec2::new(quarter as f32, 0.0)));
+            run.update_health(1.0 - quarter as f32 * 0.25);
+        }
+        run
+    }
+
+    #[test]
+    fn takes_splits_and_keeps_the_best() {
+        let mut run = TimeAttackRun::new(NORMAL, PersonalBest::default());
+        run.tick(5.0, None);
+        assert!(!run.update_health(0.8));
+        // One big hit can pass more than one mark.
+        assert!(run.update_health(0.4));
+        assert_eq!(run.splits, vec![5.0, 5.0]);
+
+        let mut first = run_against(PersonalBest::default(), 10.0);
+        assert!(first.finished());
+        let best = first.finish().expect("anything beats no best");
+        assert_eq!(best.time(), Some(40.0));
+        assert!(first.new_best);
+
+        // The clock stops at the last split.
+        first.tick(5.0, None);
+        assert_eq!(first.seconds, 40.0);
+
+        let mut slower = run_against(best.clone(), 11.0);
+        assert_eq!(slower.split_delta(0), Some(1.0));
+        assert_eq!(slower.finish(), None);
+
+        let mut faster = run_against(best, 9.0);
+        assert_eq!(faster.split_delta(3), Some(-4.0));
+        assert!(faster.finish().is_some());
+    }
+
+    #[test]
+    fn ghost_follows_the_saved_path() {
+        let best = PersonalBest {
+            splits: vec![1.0, 2.0, 3.0, 4.0],
+            ghost: vec![Vec2::ZERO, Vec2::new(10.0, 0.0)]
+        };
+        assert_eq!(best.ghost_at(0.0), Some(Vec2::ZERO));
+        assert_eq!(best.ghost_at(GHOST_DT / 2.0), Some(Vec2::new(5.0, 0.0)));
+        assert_eq!(best.ghost_at(GHOST_DT * 1.5), Some(Vec2::new(10.0, 0.0)));
+        assert_eq!(best.ghost_at(GHOST_DT * 2.0), None);
+
+        let run = run_against(PersonalBest::default(), 1.0);
+        assert_eq!(run.ghost.first(), Some(&Vec2::new(1.0, 0.0)));
+        assert_eq!(run.ghost.last(), Some(&Vec2::new(4.0, 0.0)));
+    }
+
+    #[test]
+    fn keeps_a_best_per_setting() {
+        let best = PersonalBest {
+            splits: vec![10.5, 21.0, 33.25, 41.0],
+            ghost: vec![Vec2::new(1.5, -2.0), Vec2::new(3.0, 0.25)]
+        };
+        let hard_adaptive = PersonalBestKey {
+            difficulty: Difficulty::Hard,
+            adaptive: true
+        };
+        assert_eq!(PersonalBestKey::from_name(&hard_adaptive.name()), Some(hard_adaptive));
+
+        let mut bests = PersonalBests::default();
+        bests.set(hard_adaptive, best.clone());
+        assert_eq!(bests.get(hard_adaptive), best);
+        assert_eq!(bests.get(NORMAL), PersonalBest::default());
+        assert_eq!(PersonalBests::parse(&bests.to_text()), bests);
+    }
+
+    #[test]
+    fn skips_incomplete_and_old_bests() {
+        let parsed = PersonalBests::parse("easy.splits = 1 2\neasy.ghost = 0,0\nsplits = 1 2 3 4\nmoon.splits = 1 2 3 4");
+        assert_eq!(parsed, PersonalBests::default());
+        assert_eq!(format_time(83.456), "1:23.46");
+        assert_eq!(format_delta(-0.5), "-0.50");
+    }
+}